@@ -0,0 +1,591 @@
+mod recipe_file;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use crafty::{
+    Action, BeamOptions, BeamSolver, BestFirstOptions, BestFirstSolver, CraftContext, CraftOptions,
+    ExactOptions, ExactSolver, MctsSolver, Player, ProgressCallback, Recipe, RecipeLevel,
+    RecipeLevelTable, SearchOptions, SearchProgress, Simulator, Solver,
+};
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+/// FFXIV macros can be at most 15 lines long
+const MACRO_LINES: usize = 15;
+
+#[derive(Parser)]
+#[command(
+    name = "crafty",
+    version,
+    about = "A crafting rotation solver for FFXIV"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Search for a rotation
+    Solve {
+        #[command(flatten)]
+        craft: CraftArgs,
+        #[command(flatten)]
+        search: SearchArgs,
+        /// Actions that were already used, separated by commas
+        #[arg(long, value_delimiter = ',', value_parser = parse_action)]
+        history: Vec<Action>,
+    },
+    /// Simulate a rotation step by step
+    Simulate {
+        #[command(flatten)]
+        craft: CraftArgs,
+        /// Actions to use, separated by commas. Either action names
+        /// (BasicSynthesis) or in-game labels ("Basic Synthesis") can be used
+        #[arg(required = true, value_delimiter = ',', value_parser = parse_action)]
+        actions: Vec<Action>,
+    },
+    /// Look up recipes in the bundled recipe table or a recipe file
+    Recipe {
+        /// CSV file in the same format as the bundled recipes/data/recipes.csv,
+        /// with the columns id, name, recipe_level, progress, quality, and
+        /// durability, and optionally job and is_expert. Recipes with a recipe
+        /// level that isn't bundled also need job_level, progress_div,
+        /// progress_mod, quality_div, and quality_mod, and optionally stars and
+        /// conditions_flag. Uses the bundled recipe table if not set
+        #[arg(long)]
+        recipe_file: Option<PathBuf>,
+        /// Only show the recipe with this ID
+        #[arg(long)]
+        id: Option<u32>,
+        /// Only show recipes with names matching this text. Bundled recipes
+        /// are matched loosely and sorted by how well they match
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Args)]
+struct CraftArgs {
+    #[command(flatten)]
+    player: PlayerArgs,
+    #[command(flatten)]
+    recipe: RecipeArgs,
+
+    /// Maximum number of steps in the craft
+    #[arg(long, default_value_t = 50)]
+    max_steps: u8,
+    #[arg(long)]
+    starting_quality: Option<u32>,
+    /// Quality to aim for. Defaults to the recipe's max quality
+    #[arg(long)]
+    quality_target: Option<u32>,
+    /// Allow specialist actions
+    #[arg(long)]
+    specialist: bool,
+}
+
+#[derive(Args)]
+#[command(next_help_heading = "Player")]
+struct PlayerArgs {
+    #[arg(long)]
+    job_level: u32,
+    #[arg(long)]
+    craftsmanship: u32,
+    #[arg(long)]
+    control: u32,
+    #[arg(long)]
+    cp: u32,
+}
+
+#[derive(Args)]
+#[command(next_help_heading = "Recipe")]
+struct RecipeArgs {
+    /// Look up the recipe by ID instead of passing its parameters
+    #[arg(long, conflicts_with_all = [
+        "recipe_level",
+        "progress",
+        "quality",
+        "durability",
+        "expert",
+        "recipe_job_level",
+        "stars",
+        "progress_div",
+        "progress_mod",
+        "quality_div",
+        "quality_mod",
+        "conditions_flag",
+    ])]
+    recipe_id: Option<u32>,
+    /// CSV file of recipes to look up --recipe-id in, see `crafty recipe
+    /// --help`. Uses the bundled recipe table if not set
+    #[arg(long, requires = "recipe_id")]
+    recipe_file: Option<PathBuf>,
+
+    #[arg(long, required_unless_present = "recipe_id")]
+    recipe_level: Option<u32>,
+    #[arg(long, required_unless_present = "recipe_id")]
+    progress: Option<u32>,
+    #[arg(long, required_unless_present = "recipe_id")]
+    quality: Option<u32>,
+    #[arg(long, required_unless_present = "recipe_id")]
+    durability: Option<i8>,
+    #[arg(long)]
+    expert: bool,
+
+    // The rest are taken from the bundled recipe level table if not set, and
+    // checked against it if they are
+    #[arg(long)]
+    recipe_job_level: Option<u32>,
+    #[arg(long)]
+    stars: Option<u32>,
+    #[arg(long)]
+    progress_div: Option<u32>,
+    #[arg(long)]
+    progress_mod: Option<u32>,
+    #[arg(long)]
+    quality_div: Option<u32>,
+    #[arg(long)]
+    quality_mod: Option<u32>,
+    /// Bit flags of the conditions that can occur during the craft
+    #[arg(long)]
+    conditions_flag: Option<u32>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SolverKind {
+    /// Monte Carlo tree search for the whole rotation at once
+    Oneshot,
+    /// Monte Carlo tree search for one action at a time
+    Stepwise,
+    /// Search every rotation without actions that can fail
+    Exact,
+    /// Beam search
+    Beam,
+    /// Greedy best-first search
+    BestFirst,
+}
+
+#[derive(Args)]
+#[command(next_help_heading = "Search")]
+struct SearchArgs {
+    #[arg(long, value_enum, default_value_t = SolverKind::Stepwise)]
+    solver: SolverKind,
+    /// Number of simulations to run for Monte Carlo tree searches
+    #[arg(long, default_value_t = 100_000)]
+    iterations: u32,
+    #[arg(long)]
+    seed: Option<u32>,
+    #[arg(long)]
+    threads: Option<u32>,
+    /// Stop searching after this many seconds
+    #[arg(long)]
+    time_limit: Option<f64>,
+    #[arg(long)]
+    exploration_constant: Option<f32>,
+    #[arg(long)]
+    max_score_weighting_constant: Option<f32>,
+    /// Return the shortest rotation that reaches the quality target, stopping
+    /// early once no shorter one is possible
+    #[arg(long)]
+    stop_at_quality_target: bool,
+    /// Number of states kept at each step of a beam search
+    #[arg(long, default_value_t = BeamOptions::default().width)]
+    beam_width: usize,
+    /// Maximum number of states expanded in a greedy best-first search
+    #[arg(long, default_value_t = BestFirstOptions::default().max_expansions)]
+    max_expansions: u32,
+    /// Don't print search progress
+    #[arg(long)]
+    quiet: bool,
+}
+
+fn parse_action(value: &str) -> Result<Action, String> {
+    let value = value.trim();
+    value
+        .parse()
+        .ok()
+        .or_else(|| {
+            Action::ACTIONS
+                .iter()
+                .find(|action| action.label().eq_ignore_ascii_case(value))
+                .copied()
+        })
+        .ok_or(format!("unknown action `{value}`"))
+}
+
+/// Swaps each action for the variant with the same label that the context's
+/// action pool contains, e.g. Groundwork for its traited version at level 86+
+fn resolve_actions(context: &CraftContext, actions: Vec<Action>) -> Vec<Action> {
+    actions
+        .into_iter()
+        .map(|action| {
+            if context.action_pool.contains(action) {
+                return action;
+            }
+            context
+                .action_pool
+                .iter()
+                .find(|other| other.label() == action.label())
+                .unwrap_or(action)
+        })
+        .collect()
+}
+
+/// Bundled lookups find nothing until the recipe tables are exported from the
+/// game's data
+fn note_if_nothing_bundled() {
+    if recipes::all().is_empty() {
+        eprintln!("note: no recipes are bundled yet, so use --recipe-file");
+    }
+}
+
+impl RecipeArgs {
+    fn recipe(&self) -> Result<Recipe, String> {
+        if let Some(id) = self.recipe_id {
+            let Some(recipe_file) = &self.recipe_file else {
+                note_if_nothing_bundled();
+                return recipes::by_id(id)
+                    .map(|entry| entry.recipe.clone())
+                    .ok_or(format!("recipe {id} isn't in the bundled recipe table"));
+            };
+            return recipe_file::load(recipe_file)?
+                .into_iter()
+                .find(|entry| entry.id == id)
+                .map(|entry| entry.recipe)
+                .ok_or(format!("recipe {id} isn't in {}", recipe_file.display()));
+        }
+        self.fill_recipe(recipes::recipe_level_table())
+    }
+
+    /// Creates the recipe from its parameters, filling in and checking the
+    /// ones that depend on its recipe level with `table`
+    fn fill_recipe(&self, table: &RecipeLevelTable) -> Result<Recipe, String> {
+        // clap makes sure these are set without a recipe ID
+        let recipe_level = self.recipe_level.unwrap();
+        let level = table.get(recipe_level);
+        let from_level = |value: Option<u32>, field: fn(&RecipeLevel) -> u32, arg: &str| {
+            value.or(level.map(field)).ok_or(format!(
+                "recipe level {recipe_level} isn't in the bundled recipe level table, so --{arg} is required"
+            ))
+        };
+
+        let recipe = Recipe {
+            recipe_level,
+            job_level: from_level(
+                self.recipe_job_level,
+                |level| level.job_level,
+                "recipe-job-level",
+            )?,
+            stars: from_level(self.stars, |level| level.stars, "stars").unwrap_or(0),
+            progress: self.progress.unwrap(),
+            quality: self.quality.unwrap(),
+            durability: self.durability.unwrap(),
+            progress_div: from_level(
+                self.progress_div,
+                |level| level.progress_div,
+                "progress-div",
+            )?,
+            progress_mod: from_level(
+                self.progress_mod,
+                |level| level.progress_mod,
+                "progress-mod",
+            )?,
+            quality_div: from_level(self.quality_div, |level| level.quality_div, "quality-div")?,
+            quality_mod: from_level(self.quality_mod, |level| level.quality_mod, "quality-mod")?,
+            is_expert: self.expert,
+            conditions_flag: from_level(
+                self.conditions_flag,
+                |level| level.conditions_flag,
+                "conditions-flag",
+            )
+            .unwrap_or(15),
+        };
+        if level.is_some() {
+            table.validate(&recipe).map_err(|err| err.to_string())?;
+        }
+        Ok(recipe)
+    }
+}
+
+impl CraftArgs {
+    fn context(&self) -> Result<CraftContext, String> {
+        let player = Player::new(
+            self.player.job_level,
+            self.player.craftsmanship,
+            self.player.control,
+            self.player.cp,
+        );
+        let recipe = self.recipe.recipe()?;
+        let options = CraftOptions {
+            max_steps: self.max_steps,
+            starting_quality: self.starting_quality,
+            quality_target: self.quality_target,
+            is_specialist: self.specialist,
+        };
+
+        println!("Player: {player}");
+        println!("Recipe: {recipe}");
+        CraftContext::try_new(&player, &recipe, options).map_err(|err| err.to_string())
+    }
+}
+
+impl SearchArgs {
+    fn solver(&self) -> Box<dyn Solver> {
+        let progress_callback = (!self.quiet).then(|| {
+            ProgressCallback::new(|progress: &SearchProgress| {
+                eprint!(
+                    "\r{} iterations, best score {:.4}",
+                    progress.iterations, progress.best_score
+                );
+            })
+        });
+        let search_options = SearchOptions {
+            iterations: self.iterations,
+            rng_seed: self.seed,
+            exploration_constant: self.exploration_constant,
+            max_score_weighting_constant: self.max_score_weighting_constant,
+            threads: self.threads,
+            time_limit: self.time_limit.map(Duration::from_secs_f64),
+            stop_at_quality_target: Some(self.stop_at_quality_target),
+            progress_callback,
+            ..Default::default()
+        };
+
+        match self.solver {
+            SolverKind::Oneshot => Box::new(MctsSolver::Oneshot(SearchOptions {
+                score_storage_threshold: Some(0.0),
+                ..search_options
+            })),
+            SolverKind::Stepwise => Box::new(MctsSolver::Stepwise(search_options)),
+            SolverKind::Exact => Box::new(ExactSolver(ExactOptions {
+                time_limit: search_options.time_limit,
+                ..Default::default()
+            })),
+            SolverKind::Beam => Box::new(BeamSolver(BeamOptions {
+                width: self.beam_width,
+            })),
+            SolverKind::BestFirst => Box::new(BestFirstSolver(BestFirstOptions {
+                max_expansions: self.max_expansions,
+            })),
+        }
+    }
+}
+
+/// Prints the state after each action, the result, and the rotation as macros
+fn print_rotation(context: &CraftContext, actions: &[Action]) {
+    println!();
+    for step in 1..=actions.len() {
+        let (state, _) = Simulator::simulate(context, actions[..step].to_vec());
+        println!("{:>2}. {:<24} {state}", step, actions[step - 1].label());
+    }
+
+    let (_, result) = Simulator::simulate(context, actions.to_vec());
+    match result {
+        Some(result) => println!("\nResult: {result:?}"),
+        None => println!("\nResult: unfinished"),
+    }
+
+    for (index, chunk) in actions.chunks(MACRO_LINES).enumerate() {
+        println!("\nMacro {}:", index + 1);
+        for action in chunk {
+            println!("{}", action.macro_text());
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), String> {
+    match cli.command {
+        Command::Solve {
+            craft,
+            search,
+            history,
+        } => {
+            let context = craft.context()?;
+            let history = resolve_actions(&context, history);
+            let solution = search.solver().solve(&context, history);
+            if !search.quiet {
+                eprintln!();
+            }
+            println!(
+                "Solved in {:.2?} ({} iterations), score {:.4}",
+                solution.elapsed, solution.iterations, solution.score
+            );
+            print_rotation(&context, &solution.actions);
+        }
+        Command::Simulate { craft, actions } => {
+            let context = craft.context()?;
+            print_rotation(&context, &resolve_actions(&context, actions));
+        }
+        Command::Recipe {
+            recipe_file,
+            id,
+            name,
+        } => {
+            let entries: Vec<recipe_file::RecipeEntry> = match (&recipe_file, &name) {
+                (Some(recipe_file), _) => {
+                    let name = name.map(|name| name.to_lowercase());
+                    recipe_file::load(recipe_file)?
+                        .into_iter()
+                        .filter(|entry| {
+                            name.as_ref()
+                                .is_none_or(|name| entry.name.to_lowercase().contains(name))
+                        })
+                        .collect()
+                }
+                (None, Some(name)) => recipes::search(name).into_iter().map(Into::into).collect(),
+                (None, None) => recipes::all().iter().map(Into::into).collect(),
+            };
+            if recipe_file.is_none() {
+                note_if_nothing_bundled();
+            }
+            for entry in entries {
+                if id.is_none_or(|id| entry.id == id) {
+                    println!("{:>6} {:<40} {}", entry.id, entry.name, entry.recipe);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{error::ErrorKind, CommandFactory};
+
+    #[test]
+    fn verifies_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_action_names_and_labels() {
+        assert_eq!(parse_action("BasicSynthesis"), Ok(Action::BasicSynthesis));
+        assert_eq!(parse_action("muscle memory"), Ok(Action::MuscleMemory));
+        assert!(parse_action("Basic Synth").is_err());
+    }
+
+    /// The bundled table has no rows to test with, so this has the usual
+    /// level 90 test recipe's level
+    fn level_table() -> RecipeLevelTable {
+        [RecipeLevel {
+            recipe_level: 560,
+            job_level: 90,
+            stars: 0,
+            progress: 3500,
+            quality: 7200,
+            durability: 80,
+            progress_div: 130,
+            progress_mod: 90,
+            quality_div: 115,
+            quality_mod: 80,
+            conditions_flag: 15,
+        }]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn resolves_traited_actions() {
+        let player = Player::new(90, 3304, 3374, 575);
+        let recipe = level_table().recipe(560, 0, 3500, 7200, 80, false).unwrap();
+        let context = CraftContext::new(&player, &recipe, CraftOptions::default());
+        assert_eq!(
+            resolve_actions(&context, vec![Action::Groundwork, Action::Veneration]),
+            vec![Action::GroundworkTraited, Action::Veneration]
+        );
+    }
+
+    fn recipe_args(recipe_level: &str, extra: &[&str]) -> RecipeArgs {
+        let args = [
+            &[
+                "crafty",
+                "simulate",
+                "--job-level=90",
+                "--craftsmanship=3304",
+                "--control=3374",
+                "--cp=575",
+                "--progress=3500",
+                "--quality=7200",
+                "--durability=80",
+                recipe_level,
+            ],
+            extra,
+            &["BasicSynthesis"],
+        ]
+        .concat();
+        let Command::Simulate { craft, .. } = Cli::try_parse_from(args).unwrap().command else {
+            unreachable!()
+        };
+        craft.recipe
+    }
+
+    #[test]
+    fn fills_recipe_from_level_table() {
+        let recipe = recipe_args("--recipe-level=560", &[])
+            .fill_recipe(&level_table())
+            .unwrap();
+        assert_eq!(recipe.job_level, 90);
+        assert_eq!(recipe.progress_div, 130);
+        assert_eq!(recipe.quality_mod, 80);
+        assert_eq!(recipe.conditions_flag, 15);
+
+        let err = recipe_args("--recipe-level=560", &["--quality-mod=70"])
+            .fill_recipe(&level_table())
+            .unwrap_err();
+        assert!(err.contains("quality modifier should be 80"));
+
+        let err = recipe_args("--recipe-level=561", &[])
+            .fill_recipe(&level_table())
+            .unwrap_err();
+        assert!(err.contains("--recipe-job-level is required"));
+        let extra = [
+            "--recipe-job-level=90",
+            "--progress-div=130",
+            "--progress-mod=90",
+            "--quality-div=115",
+            "--quality-mod=80",
+        ];
+        assert!(recipe_args("--recipe-level=561", &extra)
+            .fill_recipe(&level_table())
+            .is_ok());
+    }
+
+    #[test]
+    fn requires_recipe_parameters_without_id() {
+        let args = [
+            "crafty",
+            "simulate",
+            "--job-level=90",
+            "--craftsmanship=3304",
+            "--control=3374",
+            "--cp=575",
+            "BasicSynthesis",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+
+        let args = [&args[..6], &["--recipe-id=1"], &args[6..]].concat();
+        assert!(Cli::try_parse_from(&args).is_ok());
+
+        // recipe parameters would be ignored with an ID
+        for flag in [
+            "--progress=3500",
+            "--stars=1",
+            "--quality-mod=80",
+            "--expert",
+        ] {
+            let args = [&args[..7], &[flag], &args[7..]].concat();
+            let error = Cli::try_parse_from(args).err().map(|error| error.kind());
+            assert_eq!(error, Some(ErrorKind::ArgumentConflict));
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crafty::Recipe;
+use std::{fs, path::Path};
+
+/// A recipe loaded from a recipe file or the bundled recipe table, along with
+/// its ID and name
+#[derive(Debug)]
+pub struct RecipeEntry {
+    pub id: u32,
+    pub name: String,
+    pub recipe: Recipe,
+}
+
+impl From<&recipes::RecipeEntry> for RecipeEntry {
+    fn from(entry: &recipes::RecipeEntry) -> Self {
+        RecipeEntry {
+            id: entry.id,
+            name: entry.name.to_string(),
+            recipe: entry.recipe.clone(),
+        }
+    }
+}
+
+/// Loads recipes from a CSV file in the same format as the bundled
+/// `recipes.csv`, filling them in from the bundled recipe level table
+pub fn load(path: &Path) -> Result<Vec<RecipeEntry>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("couldn't read {}: {err}", path.display()))?;
+    let entries = recipes::csv::parse_recipes(&text, recipes::recipe_level_table())
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| RecipeEntry {
+            id: entry.id,
+            name: entry.name,
+            recipe: entry.recipe,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_the_bundled_recipe_file() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../recipes/data/recipes.csv");
+        let entries = load(&path).unwrap();
+        assert_eq!(entries.len(), recipes::all().len());
+        for entry in entries {
+            let bundled = recipes::by_id(entry.id).unwrap();
+            assert_eq!(entry.name, bundled.name);
+            assert_eq!(entry.recipe, bundled.recipe);
+        }
+    }
+
+    #[test]
+    fn reports_the_path() {
+        let err = load(Path::new("missing.csv")).unwrap_err();
+        assert!(err.starts_with("couldn't read missing.csv"));
+    }
+}
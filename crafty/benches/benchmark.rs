@@ -52,7 +52,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             b.iter_batched(
                 || setup_sim(Some(seed)),
                 |(context, options)| {
-                    Simulator::search_oneshot(&context, black_box(vec![]), options);
+                    Simulator::search_oneshot(&context, black_box(vec![]), options).unwrap();
                 },
                 BatchSize::SmallInput,
             )
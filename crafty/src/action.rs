@@ -1,4 +1,4 @@
-use crate::CraftState;
+use crate::{Condition, CraftState};
 use enumflags2::bitflags;
 use std::{cmp, fmt};
 
@@ -8,6 +8,8 @@ pub struct Attributes {
     pub quality_efficiency: Option<f32>,
     pub durability_cost: Option<i8>,
     pub cp_cost: Option<u32>,
+    /// Chance of the action succeeding, from 0 to 1. Always succeeds if None
+    pub success_rate: Option<f32>,
     pub effect: Option<fn(&mut CraftState)>,
 }
 
@@ -29,12 +31,13 @@ macro_rules! create_actions {
                 $(quality $quality:expr,)?
                 $(durability $durability:expr,)?
                 $(cp $cp:expr,)?
+                $(success $success:expr,)?
                 $(effect $effect:expr,)?
         )+ $(,)?
     ) => {
         #[bitflags]
-        #[repr(u32)]
-        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u64)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
         pub enum Action {
             $($action_name,)*
         }
@@ -53,6 +56,7 @@ macro_rules! create_actions {
                             quality_efficiency: optional!($( $quality )?),
                             durability_cost: optional!($( $durability )?),
                             cp_cost: optional!($( $cp )?),
+                            success_rate: optional!($( $success )?),
                             effect: optional!($( $effect )?),
                         },
                     )*
@@ -109,28 +113,54 @@ create_actions!(
         effect |state| {
             state.durability = cmp::min(state.durability + 30, state.context.durability_max);
         },
-    // HastyTouch
-    // RapidSynthesis
+    [HastyTouch, "Hasty Touch"]
+        level 9,
+        quality 1.0,
+        durability 10,
+        cp 0,
+        success 0.6,
+        effect |state| {
+            // Expedience trait
+            if state.context.player_job_level >= 96 {
+                state.buffs.expedience = 1;
+            }
+        },
+    [RapidSynthesis, "Rapid Synthesis"]
+        level 9,
+        progress 2.5,
+        durability 10,
+        cp 0,
+        success 0.5,
     [Observe, "Observe"]
         level 13,
         durability 0,  // indicates that this move is not a buff
         cp 7,
         effect |state| {
             state.observe = true;
+            // Advanced Touch can be comboed from Observe starting at level 94
+            if state.context.player_job_level >= 94 {
+                state.next_combo_action = Some(Action::AdvancedTouch);
+            }
+        },
+    [TricksOfTheTrade, "Tricks of the Trade"]
+        level 13,
+        durability 0,  // indicates that this move is not a buff
+        cp 0,
+        effect |state| {
+            state.cp = cmp::min(state.cp + 20, state.context.cp_max);
         },
-    // TricksOfTheTrade
     [WasteNot, "Waste Not"]
         level 15,
         cp 56,
         effect |state| {
-            state.buffs.waste_not = 4;
+            state.buffs.waste_not = Action::calc_buff_duration(state, 4);
             state.buffs.waste_not_ii = 0;
         },
     [Veneration, "Veneration"]
         level 15,
         cp 18,
         effect |state| {
-            state.buffs.veneration = 4;
+            state.buffs.veneration = Action::calc_buff_duration(state, 4);
         },
     [StandardTouch, "Standard Touch"]
         level 18,
@@ -146,50 +176,72 @@ create_actions!(
         level 21,
         cp 32,
         effect |state| {
-            state.buffs.great_strides = 3;
+            state.buffs.great_strides = Action::calc_buff_duration(state, 3);
         },
     [Innovation, "Innovation"]
         level 26,
         cp 18,
         effect |state| {
-            state.buffs.innovation = 4;
+            state.buffs.innovation = Action::calc_buff_duration(state, 4);
         },
     [BasicSynthesisTraited, "Basic Synthesis"]
         level 31,
         progress 1.2,
         durability 10,
-    // FinalAppraisal
+    [FinalAppraisal, "Final Appraisal"]
+        level 42,
+        cp 1,
+        effect |state| {
+            state.buffs.final_appraisal = Action::calc_buff_duration(state, 5);
+        },
     [WasteNotII, "Waste Not II"]
         level 47,
         cp 98,
         effect |state| {
             state.buffs.waste_not = 0;
-            state.buffs.waste_not_ii = 8;
+            state.buffs.waste_not_ii = Action::calc_buff_duration(state, 8);
         },
     [ByregotsBlessing, "Byregot's Blessing"]
         level 50,
         quality 0.0,  // a placeholder to indicate this action *does* affect quality
         durability 10,
         cp 24,
-    // PreciseTouch
+    [PreciseTouch, "Precise Touch"]
+        level 53,
+        quality 1.5,
+        durability 10,
+        cp 18,
     [MuscleMemory, "Muscle Memory"]
         level 54,
         progress 3.0,
         durability 10,
         cp 6,
         effect |state| {
-            state.buffs.muscle_memory = 5;
+            state.buffs.muscle_memory = Action::calc_buff_duration(state, 5);
+        },
+    [CarefulObservation, "Careful Observation"]
+        level 55,
+        durability 0,  // indicates that this move is not a buff
+        cp 0,
+        effect |state| {
+            state.careful_observation_charges -= 1;
         },
     [CarefulSynthesis, "Careful Synthesis"]
         level 62,
         progress 1.5,
         durability 10,
         cp 7,
+    [RapidSynthesisTraited, "Rapid Synthesis"]
+        level 63,
+        progress 5.0,
+        durability 10,
+        cp 0,
+        success 0.5,
     [Manipulation, "Manipulation"]
         level 65,
         cp 96,
         effect |state| {
-            state.buffs.manipulation = 8;
+            state.buffs.manipulation = Action::calc_buff_duration(state, 8);
         },
     [PrudentTouch, "Prudent Touch"]
         level 66,
@@ -227,7 +279,11 @@ create_actions!(
         quality 1.0,
         durability 10,
         cp 32,
-    // Intensive Synthesis
+    [IntensiveSynthesis, "Intensive Synthesis"]
+        level 78,
+        progress 4.0,
+        durability 10,
+        cp 6,
     [TrainedEye, "Trained Eye"]
         level 80,
         quality 0.0, // a placeholder to indicate this action *does* affect quality
@@ -251,6 +307,13 @@ create_actions!(
         progress 3.6,
         durability 20,
         cp 18,
+    [HeartAndSoul, "Heart and Soul"]
+        level 86,
+        cp 0,
+        effect |state| {
+            state.buffs.heart_and_soul = true;
+            state.heart_and_soul_charges -= 1;
+        },
     [PrudentSynthesis, "Prudent Synthesis"]
         level 88,
         progress 1.8,
@@ -260,6 +323,44 @@ create_actions!(
         level 90,
         quality 1.0,
         cp 32,
+    [RefinedTouch, "Refined Touch"]
+        level 92,
+        quality 1.0,
+        durability 10,
+        cp 24,
+    [DelicateSynthesisTraited, "Delicate Synthesis"]
+        level 94,
+        progress 1.5,
+        quality 1.0,
+        durability 10,
+        cp 32,
+    [DaringTouch, "Daring Touch"]
+        level 96,
+        quality 1.5,
+        durability 10,
+        cp 0,
+        success 0.6,
+    [QuickInnovation, "Quick Innovation"]
+        level 96,
+        cp 0,
+        effect |state| {
+            state.buffs.innovation = 1;
+            state.quick_innovation_charges -= 1;
+        },
+    [ImmaculateMend, "Immaculate Mend"]
+        level 98,
+        durability 0,  // indicates that this move is not a buff
+        cp 112,
+        effect |state| {
+            state.durability = state.context.durability_max;
+        },
+    [TrainedPerfection, "Trained Perfection"]
+        level 100,
+        cp 0,
+        effect |state| {
+            state.buffs.trained_perfection = true;
+            state.trained_perfection_charges -= 1;
+        },
 );
 
 impl Action {
@@ -276,7 +377,9 @@ impl Action {
             multiplier += 1.0;
         }
 
-        (base * efficiency * multiplier).floor() as u32
+        let condition = state.condition.progress_modifier();
+
+        (base * efficiency * multiplier * condition).floor() as u32
     }
 
     #[allow(clippy::cast_possible_truncation)]
@@ -306,22 +409,80 @@ impl Action {
 
         modifier *= multiplier;
 
-        (base * efficiency * modifier).floor() as u32
+        let condition = state.condition.quality_modifier();
+
+        (base * efficiency * condition * modifier).floor() as u32
     }
 
     pub fn calc_durability_cost(state: &CraftState, base_cost: i8) -> i8 {
+        if state.buffs.trained_perfection {
+            return 0;
+        }
+
+        let mut divider = 1;
         if state.buffs.waste_not > 0 || state.buffs.waste_not_ii > 0 {
-            return base_cost / 2;
+            divider *= 2;
         }
-        base_cost
+        if state.condition == Condition::Sturdy {
+            divider *= 2;
+        }
+        // costs are always rounded up
+        (base_cost + divider - 1) / divider
     }
 
     pub fn calc_cp_cost(state: &CraftState, base_cost: u32) -> u32 {
         // test for basic touch combo
-        if state.action.is_some() && state.action == state.next_combo_action {
-            return 18;
+        let cost = if state.action.is_some() && state.action == state.next_combo_action {
+            18
+        } else {
+            base_cost
+        };
+
+        if state.condition == Condition::Pliant {
+            return cost.div_ceil(2);
         }
-        base_cost
+        cost
+    }
+
+    pub fn calc_success_rate(state: &CraftState, base_rate: f32) -> f32 {
+        if state.condition == Condition::Centered {
+            return f32::min(base_rate + 0.25, 1.0);
+        }
+        base_rate
+    }
+
+    /// Whether the action is used without ticking buff timers
+    pub fn skips_buff_ticks(&self) -> bool {
+        matches!(
+            self,
+            Action::FinalAppraisal
+                | Action::QuickInnovation
+                | Action::HeartAndSoul
+                | Action::CarefulObservation
+        )
+    }
+
+    /// Whether a new condition is rolled after using the action
+    pub fn changes_condition(&self) -> bool {
+        !matches!(
+            self,
+            Action::FinalAppraisal | Action::QuickInnovation | Action::HeartAndSoul
+        )
+    }
+
+    /// Whether the action can only be used by specialists
+    pub fn is_specialist_only(&self) -> bool {
+        matches!(
+            self,
+            Action::CarefulObservation | Action::HeartAndSoul | Action::QuickInnovation
+        )
+    }
+
+    pub fn calc_buff_duration(state: &CraftState, base_duration: u8) -> u8 {
+        if state.condition == Condition::Primed {
+            return base_duration + 2;
+        }
+        base_duration
     }
 
     pub fn macro_text(&self) -> String {
@@ -345,3 +506,20 @@ impl fmt::Display for Action {
         write!(f, "{}", self.label())
     }
 }
+
+/// Actions are serialized by name, e.g. `BasicSynthesis`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Action {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Action {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map_err(|_| serde::de::Error::custom(format!("unknown action `{name}`")))
+    }
+}
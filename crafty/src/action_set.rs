@@ -34,7 +34,7 @@ impl<T: BitFlag + RawBitFlags<Numeric = u64>> BitFlagNth<T> for BitFlags<T, u64>
 
 impl<T: BitFlag + RawBitFlags<Numeric = u32>> BitFlagExt<T> for BitFlags<T, u32> {
     fn keep<F: Fn(T) -> bool>(&mut self, f: F) {
-        for item in self.iter() {
+        for item in *self {
             if !f(item) {
                 self.remove(item);
             }
@@ -42,7 +42,7 @@ impl<T: BitFlag + RawBitFlags<Numeric = u32>> BitFlagExt<T> for BitFlags<T, u32>
     }
 
     fn sample(&self, rng: &mut SmallRng) -> T {
-        self.nth(rng.gen_range(0..self.len()) as u32)
+        self.nth(rng.gen_range(0..self.bits().count_ones()))
     }
 
     fn pick(&mut self, rng: &mut SmallRng) -> T {
@@ -54,7 +54,7 @@ impl<T: BitFlag + RawBitFlags<Numeric = u32>> BitFlagExt<T> for BitFlags<T, u32>
 
 impl<T: BitFlag + RawBitFlags<Numeric = u64>> BitFlagExt<T> for BitFlags<T, u64> {
     fn keep<F: Fn(T) -> bool>(&mut self, f: F) {
-        for item in self.iter() {
+        for item in *self {
             if !f(item) {
                 self.remove(item);
             }
@@ -62,7 +62,7 @@ impl<T: BitFlag + RawBitFlags<Numeric = u64>> BitFlagExt<T> for BitFlags<T, u64>
     }
 
     fn sample(&self, rng: &mut SmallRng) -> T {
-        self.nth(rng.gen_range(0..self.len()) as u32)
+        self.nth(rng.gen_range(0..self.bits().count_ones()))
     }
 
     fn pick(&mut self, rng: &mut SmallRng) -> T {
@@ -122,12 +122,12 @@ mod tests {
                 [BasicTouch, BasicSynthesis, GreatStrides, TrainedFinesse].contains(&random_action)
             );
 
-            counts[(random_action as u32).ilog2() as usize] += 1;
+            counts[(random_action as u64).ilog2() as usize] += 1;
         }
 
-        assert!(counts[(BasicTouch as u32).ilog2() as usize] > 0);
-        assert!(counts[(BasicSynthesis as u32).ilog2() as usize] > 0);
-        assert!(counts[(GreatStrides as u32).ilog2() as usize] > 0);
-        assert!(counts[(TrainedFinesse as u32).ilog2() as usize] > 0);
+        assert!(counts[(BasicTouch as u64).ilog2() as usize] > 0);
+        assert!(counts[(BasicSynthesis as u64).ilog2() as usize] > 0);
+        assert!(counts[(GreatStrides as u64).ilog2() as usize] > 0);
+        assert!(counts[(TrainedFinesse as u64).ilog2() as usize] > 0);
     }
 }
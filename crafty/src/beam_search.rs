@@ -0,0 +1,189 @@
+use crate::{Action, CraftContext, CraftResult, CraftState, Simulator};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BeamOptions {
+    /// Number of states kept at each step
+    pub width: usize,
+}
+
+impl Default for BeamOptions {
+    fn default() -> Self {
+        Self { width: 500 }
+    }
+}
+
+/// The score of a state after finishing the craft as quickly as possible,
+/// greedily picking the progress action with the best score that doesn't fail
+/// the craft each step. Strict move pruning isn't applied, since it would rule
+/// out finishing early. Zero if the craft can't be finished this way. Scoring
+/// states by how they'd finish keeps searches from chasing quality they can't
+/// afford to finish with.
+pub(crate) fn completion_score(state: &CraftState) -> f32 {
+    let mut state = state.clone_deterministic();
+    loop {
+        match state.check_result() {
+            Some(CraftResult::Finished(score)) => return score,
+            Some(_) => return 0.0,
+            None => {}
+        }
+
+        let next_state = state
+            .available_moves
+            .iter()
+            .filter(|action| action.attributes().progress_efficiency.is_some())
+            .map(|action| state.execute_deterministic(&action))
+            .filter(|next_state| {
+                matches!(
+                    next_state.check_result(),
+                    None | Some(CraftResult::Finished(_))
+                )
+            })
+            .max_by(|a, b| a.score().total_cmp(&b.score()));
+        match next_state {
+            Some(next_state) => state = next_state,
+            None => return 0.0,
+        }
+    }
+}
+
+/// A beam search over strict moves. Every state in the beam is expanded one
+/// step at a time, and only the best `width` states by `completion_score` are
+/// kept for the next step. Runtime is bounded by the width, the number of
+/// available moves, and the step limit.
+///
+/// Unfinished states are ranked by `completion_score` rather than
+/// `CraftState::score`, which gives them credit for quality even when there's
+/// no durability or CP left to finish the craft with. That would fill the beam
+/// with states that can only fail. Finished crafts are still compared by
+/// their score.
+pub struct BeamSearch;
+
+impl BeamSearch {
+    /// Searches for a rotation after `action_history`, returning the finished
+    /// craft with the highest score. If no craft was finished, the best
+    /// unfinished state from the last step is returned instead. If
+    /// `action_history` already ends the craft, it's returned as is rather
+    /// than as an error like `Simulator::search_oneshot` does.
+    pub fn solve(
+        context: &CraftContext,
+        action_history: Vec<Action>,
+        options: BeamOptions,
+    ) -> (Vec<Action>, CraftState<'_>) {
+        let (actions, state, _) = Self::search(context, action_history, options);
+        (actions, state)
+    }
+
+    /// Same as `solve`, but also returns the number of states expanded.
+    pub(crate) fn search<'a>(
+        context: &'a CraftContext,
+        action_history: Vec<Action>,
+        options: BeamOptions,
+    ) -> (Vec<Action>, CraftState<'a>, u64) {
+        let (start_state, result) = Simulator::simulate(context, action_history.clone());
+        if result.is_some() {
+            return (action_history, start_state, 0);
+        }
+
+        let mut best_finished: Option<(f32, Vec<Action>, CraftState<'a>)> = None;
+        let mut beam = vec![(action_history, start_state.clone_strict())];
+        let mut expansions = 0;
+
+        loop {
+            let mut candidates = vec![];
+            expansions += beam.len() as u64;
+            for (actions, state) in &beam {
+                for action in state.available_moves {
+                    let next_state = state.execute_strict(&action);
+                    let next_actions = [actions.as_slice(), &[action]].concat();
+                    match next_state.check_result() {
+                        None => candidates.push((next_actions, next_state)),
+                        Some(CraftResult::Finished(score)) => {
+                            if best_finished
+                                .as_ref()
+                                .is_none_or(|(best_score, _, _)| score > *best_score)
+                            {
+                                best_finished = Some((score, next_actions, next_state));
+                            }
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+
+            if candidates.is_empty() {
+                break;
+            }
+
+            // states reached by different action orders only need to be kept once
+            let mut seen = HashSet::new();
+            candidates.retain(|(_, state)| seen.insert(state.key()));
+            let mut candidates: Vec<_> = candidates
+                .into_iter()
+                .map(|candidate| (completion_score(&candidate.1), candidate))
+                .collect();
+            candidates.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+            candidates.truncate(options.width.max(1));
+            beam = candidates
+                .into_iter()
+                .map(|(_, candidate)| candidate)
+                .collect();
+        }
+
+        let (actions, state) = match best_finished {
+            Some((_, actions, state)) => (actions, state),
+            None => beam.swap_remove(0),
+        };
+        (actions, state, expansions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    #[test]
+    fn finishes_craft() {
+        let context = test_fixtures::context();
+        let options = BeamOptions { width: 50 };
+        let (actions, state) = BeamSearch::solve(&context, vec![], options);
+        assert!(state.progress >= context.progress_target);
+
+        let (simulated, _) = Simulator::simulate(&context, actions);
+        assert_eq!(simulated.quality, state.quality);
+    }
+
+    #[test]
+    fn wider_beams_do_not_score_lower() {
+        let context = test_fixtures::context();
+        let (_, narrow) = BeamSearch::solve(&context, vec![], BeamOptions { width: 1 });
+        let (_, wide) = BeamSearch::solve(&context, vec![], BeamOptions { width: 20 });
+        assert!(narrow.progress >= context.progress_target);
+        assert!(wide.score() >= narrow.score());
+    }
+
+    #[test]
+    fn completion_score_finishes_craft() {
+        let context = test_fixtures::context();
+        let state = CraftState::new_strict(&context);
+        assert!(completion_score(&state) > 0.0);
+    }
+
+    #[test]
+    fn completion_score_is_zero_when_craft_cant_finish() {
+        let context = test_fixtures::context();
+        // one more synthesis would break the item before finishing it
+        let (state, _) = Simulator::simulate(&context, vec![Action::BasicTouch; 7]);
+        assert!(state.score() > 0.0);
+        assert!(completion_score(&state) == 0.0);
+    }
+
+    #[test]
+    fn keeps_action_history() {
+        let context = test_fixtures::context();
+        let history = vec![Action::MuscleMemory, Action::Veneration];
+        let (actions, _) = BeamSearch::solve(&context, history.clone(), BeamOptions { width: 10 });
+        assert_eq!(actions[..2], history[..]);
+    }
+}
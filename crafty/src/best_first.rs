@@ -0,0 +1,202 @@
+use crate::{Action, CraftContext, CraftResult, CraftState, Simulator};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct BestFirstOptions {
+    /// Maximum number of states to expand before giving up
+    pub max_expansions: u32,
+}
+
+impl Default for BestFirstOptions {
+    fn default() -> Self {
+        Self {
+            max_expansions: 100_000,
+        }
+    }
+}
+
+/// A state waiting to be expanded, ordered by its own score. Ties go to the
+/// state that was queued first so that searches are reproducible.
+struct Candidate {
+    score: f32,
+    order: u32,
+    index: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+/// A greedy best-first search over strict moves. The unexpanded state with
+/// the highest `CraftState::score` is always expanded next, without counting
+/// the steps it took to get there, so the first finished craft isn't
+/// necessarily the best one. States that were already reached through another
+/// action order are skipped. The search stops once `max_expansions` states
+/// have been expanded, or once there's nothing left to expand.
+pub struct BestFirstSearch;
+
+impl BestFirstSearch {
+    /// Searches for a rotation after `action_history`, returning the finished
+    /// craft with the highest score. If no craft was finished, the unfinished
+    /// state with the highest score is returned instead.
+    pub fn solve(
+        context: &CraftContext,
+        action_history: Vec<Action>,
+        options: BestFirstOptions,
+    ) -> (Vec<Action>, CraftState<'_>) {
+        let (actions, state, _) = Self::search(context, action_history, options);
+        (actions, state)
+    }
+
+    /// Same as `solve`, but also returns the number of states expanded.
+    pub(crate) fn search<'a>(
+        context: &'a CraftContext,
+        action_history: Vec<Action>,
+        options: BestFirstOptions,
+    ) -> (Vec<Action>, CraftState<'a>, u64) {
+        let (start_state, result) = Simulator::simulate(context, action_history.clone());
+        if result.is_some() {
+            return (action_history, start_state, 0);
+        }
+
+        // each state along with the index of the state it was reached from, and
+        // the action used
+        let start_state = start_state.clone_strict();
+        let mut nodes: Vec<(CraftState<'a>, Option<(usize, Action)>)> = vec![(start_state, None)];
+        let mut queue = BinaryHeap::from([Candidate {
+            score: nodes[0].0.score(),
+            order: 0,
+            index: 0,
+        }]);
+        let mut seen = HashSet::from([nodes[0].0.key()]);
+
+        let mut best_finished: Option<(f32, usize)> = None;
+        let mut best_unfinished = (nodes[0].0.score(), 0);
+        let mut expansions = 0;
+
+        while let Some(Candidate { index, .. }) = queue.pop() {
+            if expansions >= options.max_expansions {
+                break;
+            }
+            expansions += 1;
+
+            for action in nodes[index].0.available_moves {
+                let next_state = nodes[index].0.execute_strict(&action);
+                if !seen.insert(next_state.key()) {
+                    continue;
+                }
+
+                let result = next_state.check_result();
+                let score = next_state.score();
+                let next_index = nodes.len();
+                nodes.push((next_state, Some((index, action))));
+
+                match result {
+                    None => {
+                        if score > best_unfinished.0 {
+                            best_unfinished = (score, next_index);
+                        }
+                        queue.push(Candidate {
+                            score,
+                            order: u32::try_from(next_index).unwrap_or(u32::MAX),
+                            index: next_index,
+                        });
+                    }
+                    Some(CraftResult::Finished(score)) => {
+                        if best_finished.is_none_or(|(best_score, _)| score > best_score) {
+                            best_finished = Some((score, next_index));
+                        }
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        let (_, mut index) = best_finished.unwrap_or(best_unfinished);
+        let state = nodes[index].0.clone();
+        let mut actions = vec![];
+        while let (_, Some((parent_index, action))) = nodes[index] {
+            actions.push(action);
+            index = parent_index;
+        }
+        actions.reverse();
+
+        (
+            [action_history, actions].concat(),
+            state,
+            u64::from(expansions),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    #[test]
+    fn finishes_craft() {
+        let context = test_fixtures::context();
+        let options = BestFirstOptions {
+            max_expansions: 5_000,
+        };
+        let (actions, state) = BestFirstSearch::solve(&context, vec![], options);
+        assert!(state.progress >= context.progress_target);
+
+        let (simulated, _) = Simulator::simulate(&context, actions);
+        assert_eq!(simulated.progress, state.progress);
+        assert_eq!(simulated.quality, state.quality);
+    }
+
+    #[test]
+    fn returns_best_unfinished_state() {
+        let context = test_fixtures::context();
+        let options = BestFirstOptions { max_expansions: 1 };
+        let (actions, state) = BestFirstSearch::solve(&context, vec![], options);
+        assert_eq!(actions.len(), 1);
+        assert!(state.score() > CraftState::new(&context).score());
+    }
+
+    #[test]
+    fn candidates_are_ordered_by_score() {
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate {
+            score: 0.5,
+            order: 0,
+            index: 0,
+        });
+        queue.push(Candidate {
+            score: 0.7,
+            order: 1,
+            index: 1,
+        });
+        queue.push(Candidate {
+            score: 0.7,
+            order: 2,
+            index: 2,
+        });
+        let order: Vec<usize> = std::iter::from_fn(|| queue.pop().map(|c| c.index)).collect();
+        assert_eq!(order, vec![1, 2, 0]);
+    }
+}
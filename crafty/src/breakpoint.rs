@@ -0,0 +1,206 @@
+use crate::{
+    Action, CraftContext, CraftContextError, CraftOptions, CraftResult, Player, Recipe, Simulator,
+};
+
+/// A stat value along with the factor it produces
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Breakpoint {
+    pub value: u32,
+    pub factor: f32,
+}
+
+/// The lowest stats at which a rotation still finishes the craft and reaches
+/// the quality target, with each stat checked while the others are left at
+/// the player's values. A stat is None if the rotation fails at the player's
+/// current stats.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoints {
+    pub craftsmanship: Option<Breakpoint>,
+    pub control: Option<Breakpoint>,
+    pub cp: Option<u32>,
+    /// The next craftsmanship value above the player's where the progress
+    /// factor increases
+    pub next_craftsmanship: Option<Breakpoint>,
+    /// The next control value above the player's where the quality factor
+    /// increases
+    pub next_control: Option<Breakpoint>,
+}
+
+/// Smallest value in `low..=high` for which `predicate` holds, assuming it is
+/// monotonic. Returns None if it doesn't hold for `high`.
+fn binary_search(mut low: u32, mut high: u32, predicate: impl Fn(u32) -> bool) -> Option<u32> {
+    if !predicate(high) {
+        return None;
+    }
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if predicate(mid) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Some(low)
+}
+
+impl Breakpoints {
+    /// Finds the minimum stats needed for `actions` to finish the craft and
+    /// reach the quality target. Since `CraftContext` floors the progress and
+    /// quality factors, craftsmanship and control only matter at the values
+    /// where a factor changes, and every returned craftsmanship and control
+    /// value is the first one to reach its factor.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CraftContextError` if a context can't be created for the
+    /// player and recipe.
+    pub fn find(
+        player: &Player,
+        recipe: &Recipe,
+        options: CraftOptions,
+        actions: &[Action],
+    ) -> Result<Self, CraftContextError> {
+        let context = CraftContext::try_new(player, recipe, options)?;
+        let with_stats = |craftsmanship: u32, control: u32, cp: u32| {
+            Player::new(player.job_level, craftsmanship, control, cp)
+        };
+        let succeeds = |player: &Player| {
+            let context = CraftContext::new(player, recipe, options);
+            match Simulator::simulate(&context, actions.to_vec()) {
+                (state, Some(CraftResult::Finished(_))) => state.quality >= context.quality_target,
+                _ => false,
+            }
+        };
+        let progress_factor = |craftsmanship| {
+            CraftContext::new(&with_stats(craftsmanship, 0, 0), recipe, options).progress_factor
+        };
+        let quality_factor =
+            |control| CraftContext::new(&with_stats(0, control, 0), recipe, options).quality_factor;
+
+        let craftsmanship = binary_search(0, player.craftsmanship, |craftsmanship| {
+            succeeds(&with_stats(craftsmanship, player.control, player.cp))
+        })
+        .map(|value| Breakpoint {
+            value,
+            factor: progress_factor(value),
+        });
+        let control = binary_search(0, player.control, |control| {
+            succeeds(&with_stats(player.craftsmanship, control, player.cp))
+        })
+        .map(|value| Breakpoint {
+            value,
+            factor: quality_factor(value),
+        });
+        let cp = binary_search(0, player.cp, |cp| {
+            succeeds(&with_stats(player.craftsmanship, player.control, cp))
+        });
+
+        // Each factor goes up by at least one every `div * 10` points as long
+        // as the recipe's modifier is at least 10%
+        let next_craftsmanship = binary_search(
+            player.craftsmanship + 1,
+            player.craftsmanship + recipe.progress_div * 10,
+            |craftsmanship| progress_factor(craftsmanship) > context.progress_factor,
+        )
+        .map(|value| Breakpoint {
+            value,
+            factor: progress_factor(value),
+        });
+        let next_control = binary_search(
+            player.control + 1,
+            player.control + recipe.quality_div * 10,
+            |control| quality_factor(control) > context.quality_factor,
+        )
+        .map(|value| Breakpoint {
+            value,
+            factor: quality_factor(value),
+        });
+
+        Ok(Self {
+            craftsmanship,
+            control,
+            cp,
+            next_craftsmanship,
+            next_control,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, recipe};
+    use Action::*;
+
+    fn options() -> CraftOptions {
+        CraftOptions {
+            max_steps: 25,
+            quality_target: Some(600),
+            ..Default::default()
+        }
+    }
+
+    fn actions() -> Vec<Action> {
+        vec![
+            MuscleMemory,
+            Veneration,
+            BasicTouch,
+            StandardTouch,
+            GroundworkTraited,
+            GroundworkTraited,
+            BasicSynthesisTraited,
+        ]
+    }
+
+    #[test]
+    fn finds_minimum_stats() {
+        let player = test_fixtures::player();
+        let breakpoints = Breakpoints::find(&player, &recipe(), options(), &actions()).unwrap();
+
+        let craftsmanship = breakpoints.craftsmanship.unwrap();
+        let below = Player {
+            craftsmanship: craftsmanship.value - 1,
+            ..player
+        };
+        let context = CraftContext::new(&below, &recipe(), options());
+        assert!(context.progress_factor < craftsmanship.factor);
+        let (_, result) = Simulator::simulate(&context, actions());
+        assert!(!matches!(result, Some(CraftResult::Finished(_))));
+
+        let control = breakpoints.control.unwrap();
+        assert!(control.value < player.control);
+        let cp = breakpoints.cp.unwrap();
+        // Muscle Memory, then Veneration, Basic Touch, Standard Touch in a
+        // combo, and two Groundworks
+        assert_eq!(cp, 6 + 18 * 5);
+    }
+
+    #[test]
+    fn finds_next_factor_thresholds() {
+        let player = test_fixtures::player();
+        let context = CraftContext::new(&player, &recipe(), options());
+        let breakpoints = Breakpoints::find(&player, &recipe(), options(), &actions()).unwrap();
+
+        let next = breakpoints.next_craftsmanship.unwrap();
+        assert!(next.value > player.craftsmanship);
+        assert!(next.factor > context.progress_factor);
+        let before = Player {
+            craftsmanship: next.value - 1,
+            ..player
+        };
+        let before_factor = CraftContext::new(&before, &recipe(), options()).progress_factor;
+        assert!((before_factor - context.progress_factor).abs() < f32::EPSILON);
+
+        let next = breakpoints.next_control.unwrap();
+        assert!(next.factor > context.quality_factor);
+    }
+
+    #[test]
+    fn missing_breakpoints_when_rotation_fails() {
+        let player = Player::new(90, 100, 100, 575);
+        let breakpoints = Breakpoints::find(&player, &recipe(), options(), &actions()).unwrap();
+        assert_eq!(breakpoints.craftsmanship, None);
+        assert_eq!(breakpoints.control, None);
+        assert_eq!(breakpoints.cp, None);
+    }
+}
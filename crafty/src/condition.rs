@@ -0,0 +1,196 @@
+use crate::CraftContext;
+use enumflags2::{bitflags, BitFlags};
+use std::fmt;
+
+/// The condition of the current step. Bit positions match the game's
+/// `RecipeLevelTable.ConditionsFlag`, so a recipe's `conditions_flag` can be
+/// converted directly into a `ConditionSet`.
+#[bitflags]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Condition {
+    Normal,
+    /// Increases quality by 50%
+    Good,
+    /// Increases quality by 300%; always followed by Poor
+    Excellent,
+    /// Decreases quality by 50%
+    Poor,
+    /// Increases success rate by 25%
+    Centered,
+    /// Reduces durability loss by 50%
+    Sturdy,
+    /// Reduces CP cost by 50%
+    Pliant,
+    /// Increases progress by 50%
+    Malleable,
+    /// Increases the duration of buffs applied this step by 2 steps
+    Primed,
+    /// Always followed by Good
+    GoodOmen,
+}
+
+pub type ConditionSet = BitFlags<Condition>;
+
+impl Condition {
+    pub fn quality_modifier(self) -> f32 {
+        match self {
+            Condition::Good => 1.5,
+            Condition::Excellent => 4.0,
+            Condition::Poor => 0.5,
+            _ => 1.0,
+        }
+    }
+
+    pub fn progress_modifier(self) -> f32 {
+        match self {
+            Condition::Malleable => 1.5,
+            _ => 1.0,
+        }
+    }
+
+    /// The condition that is guaranteed to follow this one, if any
+    pub fn forced_next(self) -> Option<Condition> {
+        match self {
+            Condition::Excellent => Some(Condition::Poor),
+            Condition::GoodOmen => Some(Condition::Good),
+            _ => None,
+        }
+    }
+
+    /// Possible conditions for the next step, along with their probabilities.
+    /// Rates are taken from the Teamcraft simulator; Poor can only follow
+    /// Excellent.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn next_conditions(self, context: &CraftContext) -> Vec<(Condition, f32)> {
+        if let Some(condition) = self.forced_next() {
+            return vec![(condition, 1.0)];
+        }
+
+        let mut outcomes = vec![];
+        let mut normal_rate = 100;
+        for condition in context.conditions {
+            let rate = match condition {
+                Condition::Good if context.is_expert => 12,
+                // Quality Assurance trait
+                Condition::Good if context.player_job_level >= 63 => 25,
+                Condition::Good => 20,
+                Condition::Excellent if context.is_expert => 0,
+                Condition::Excellent => 4,
+                Condition::Centered | Condition::Sturdy => 15,
+                Condition::Pliant
+                | Condition::Malleable
+                | Condition::Primed
+                | Condition::GoodOmen => 12,
+                Condition::Normal | Condition::Poor => 0,
+            };
+            if rate > 0 {
+                outcomes.push((condition, rate as f32 / 100.0));
+                normal_rate -= rate;
+            }
+        }
+        outcomes.insert(0, (Condition::Normal, normal_rate as f32 / 100.0));
+
+        outcomes
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Condition::Normal => "Normal",
+            Condition::Good => "Good",
+            Condition::Excellent => "Excellent",
+            Condition::Poor => "Poor",
+            Condition::Centered => "Centered",
+            Condition::Sturdy => "Sturdy",
+            Condition::Pliant => "Pliant",
+            Condition::Malleable => "Malleable",
+            Condition::Primed => "Primed",
+            Condition::GoodOmen => "Good Omen",
+        }
+    }
+
+    /// Converts a recipe's `conditions_flag` into the set of conditions that
+    /// can occur during the craft. Normal is always possible.
+    pub fn from_flag(conditions_flag: u32) -> ConditionSet {
+        ConditionSet::from_bits_truncate(conditions_flag) | Condition::Normal
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Condition;
+    use crate::{test_fixtures, CraftContext, CraftOptions, Recipe};
+    use Condition::*;
+
+    fn context(conditions_flag: u32, is_expert: bool) -> CraftContext {
+        let recipe = Recipe {
+            is_expert,
+            conditions_flag,
+            ..test_fixtures::recipe()
+        };
+        let craft_options = CraftOptions {
+            max_steps: 25,
+            ..Default::default()
+        };
+        CraftContext::new(&test_fixtures::player(), &recipe, craft_options)
+    }
+
+    fn probability_of(outcomes: &[(Condition, f32)], condition: Condition) -> f32 {
+        outcomes
+            .iter()
+            .find(|(c, _)| *c == condition)
+            .map_or(0.0, |(_, p)| *p)
+    }
+
+    #[test]
+    fn parses_normal_recipe_flag() {
+        let conditions = Condition::from_flag(15);
+        assert_eq!(conditions.len(), 4);
+        assert!(conditions.contains(Normal | Good | Excellent | Poor));
+    }
+
+    #[test]
+    fn parses_expert_recipe_flag() {
+        let conditions = Condition::from_flag(115);
+        assert_eq!(conditions.len(), 5);
+        assert!(conditions.contains(Normal | Good | Centered | Sturdy | Pliant));
+
+        let conditions = Condition::from_flag(483);
+        assert!(conditions.contains(Normal | Good | Sturdy | Pliant | Malleable | Primed));
+        assert!(!conditions.contains(Excellent));
+    }
+
+    #[test]
+    fn normal_is_always_possible() {
+        assert_eq!(Condition::from_flag(0), Normal);
+    }
+
+    #[test]
+    fn normal_recipe_probabilities() {
+        let context = context(15, false);
+        let outcomes = Normal.next_conditions(&context);
+        assert_eq!(outcomes.len(), 3);
+        assert!((probability_of(&outcomes, Normal) - 0.71).abs() < 1e-6);
+        assert!((probability_of(&outcomes, Good) - 0.25).abs() < 1e-6);
+        assert!((probability_of(&outcomes, Excellent) - 0.04).abs() < 1e-6);
+        assert_eq!(Excellent.next_conditions(&context), vec![(Poor, 1.0)]);
+    }
+
+    #[test]
+    fn expert_recipe_probabilities() {
+        let context = context(995, true);
+        let outcomes = Normal.next_conditions(&context);
+        let total: f32 = outcomes.iter().map(|(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+        assert!((probability_of(&outcomes, Normal) - 0.25).abs() < 1e-6);
+        assert!((probability_of(&outcomes, Sturdy) - 0.15).abs() < 1e-6);
+        assert_eq!(GoodOmen.next_conditions(&context), vec![(Good, 1.0)]);
+    }
+}
@@ -0,0 +1,79 @@
+use std::cmp;
+
+/// A stat bonus that's a percentage of the player's stat, up to a maximum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatBonus {
+    pub percent: u32,
+    pub max: u32,
+}
+
+impl StatBonus {
+    pub fn new(percent: u32, max: u32) -> Self {
+        Self { percent, max }
+    }
+
+    pub fn calc(&self, stat: u32) -> u32 {
+        cmp::min(stat * self.percent / 100, self.max)
+    }
+}
+
+/// A meal or tincture. Each bonus is calculated from the player's stats
+/// without other consumables applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Consumable {
+    pub craftsmanship: Option<StatBonus>,
+    pub control: Option<StatBonus>,
+    pub cp: Option<StatBonus>,
+}
+
+impl Consumable {
+    pub fn new(
+        craftsmanship: Option<StatBonus>,
+        control: Option<StatBonus>,
+        cp: Option<StatBonus>,
+    ) -> Self {
+        Self {
+            craftsmanship,
+            control,
+            cp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Consumable, StatBonus};
+    use crate::{test_fixtures, Player};
+
+    #[test]
+    fn bonus_is_capped() {
+        let bonus = StatBonus::new(5, 90);
+        assert_eq!(bonus.calc(1000), 50);
+        assert_eq!(bonus.calc(3000), 90);
+    }
+
+    #[test]
+    fn applies_meal_and_tincture() {
+        let player = test_fixtures::player();
+        let meal = Consumable::new(
+            None,
+            Some(StatBonus::new(5, 97)),
+            Some(StatBonus::new(26, 92)),
+        );
+        let tincture = Consumable::new(None, Some(StatBonus::new(4, 87)), None);
+
+        let player = player.with_consumables(&[meal, tincture]);
+        assert_eq!(player.craftsmanship, 3304);
+        assert_eq!(player.control, 3374 + 97 + 87);
+        assert_eq!(player.cp, 575 + 92);
+    }
+
+    #[test]
+    fn bonuses_use_base_stats() {
+        let player = Player::new(90, 1000, 1000, 500);
+        let meal = Consumable::new(Some(StatBonus::new(10, 1000)), None, None);
+
+        let player = player.with_consumables(&[meal, meal]);
+        assert_eq!(player.craftsmanship, 1200);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{Action, ActionSet, Player, Recipe};
+use crate::{Action, ActionSet, Condition, ConditionSet, Player, Recipe};
+use std::{error, fmt};
 
 #[derive(Debug, Clone)]
 pub struct CraftContext {
@@ -15,21 +16,90 @@ pub struct CraftContext {
     pub durability_max: i8,
     pub cp_max: u32,
     pub is_expert: bool,
+    pub is_specialist: bool,
+    /// Conditions that can occur during the craft
+    pub conditions: ConditionSet,
     pub action_pool: ActionSet,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct CraftOptions {
+    /// Defaults to 50 when deserializing
+    #[cfg_attr(feature = "serde", serde(default = "default_max_steps"))]
     pub max_steps: u8,
     pub starting_quality: Option<u32>,
     pub quality_target: Option<u32>,
+    /// Specialists can use Careful Observation, Heart and Soul, and Quick
+    /// Innovation
+    pub is_specialist: bool,
 }
 
-fn get_player_clvl(player_job_level: u32) -> Option<u32> {
-    if player_job_level <= 50 {
-        None
+#[cfg(feature = "serde")]
+fn default_max_steps() -> u8 {
+    50
+}
+
+/// Reasons a `CraftContext` can't be created from a player and recipe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CraftContextError {
+    /// The player's job level is outside of the supported range
+    UnsupportedJobLevel(u32),
+    /// The recipe's progress or quality divider is zero
+    ZeroDivider,
+    /// The recipe doesn't require any progress to complete
+    ZeroProgress,
+    /// The recipe's durability isn't positive
+    InvalidDurability(i8),
+    /// The quality target is higher than the recipe's max quality
+    QualityTargetTooHigh {
+        quality_target: u32,
+        max_quality: u32,
+    },
+    /// The starting quality is higher than the recipe's max quality
+    StartingQualityTooHigh {
+        starting_quality: u32,
+        max_quality: u32,
+    },
+}
+
+impl fmt::Display for CraftContextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnsupportedJobLevel(level) => write!(f, "job level {level} is not supported"),
+            Self::ZeroDivider => write!(f, "recipe progress and quality dividers must be non-zero"),
+            Self::ZeroProgress => write!(f, "recipe progress must be non-zero"),
+            Self::InvalidDurability(durability) => {
+                write!(f, "recipe durability must be positive, got {durability}")
+            }
+            Self::QualityTargetTooHigh {
+                quality_target,
+                max_quality,
+            } => write!(
+                f,
+                "quality target {quality_target} is higher than the recipe's max quality {max_quality}"
+            ),
+            Self::StartingQualityTooHigh {
+                starting_quality,
+                max_quality,
+            } => write!(
+                f,
+                "starting quality {starting_quality} is higher than the recipe's max quality {max_quality}"
+            ),
+        }
+    }
+}
+
+impl error::Error for CraftContextError {}
+
+fn get_player_clvl(player_job_level: u32) -> Result<Option<u32>, CraftContextError> {
+    if player_job_level == 0 {
+        Err(CraftContextError::UnsupportedJobLevel(player_job_level))
+    } else if player_job_level <= 50 {
+        Ok(None)
     } else {
-        Some(match player_job_level {
+        Ok(Some(match player_job_level {
             51 => 120,
             52 => 125,
             53 => 130,
@@ -70,14 +140,24 @@ fn get_player_clvl(player_job_level: u32) -> Option<u32> {
             88 => 550,
             89 => 555,
             90 => 560,
-            _ => unimplemented!(),
-        })
+            91 => 650,
+            92 => 653,
+            93 => 656,
+            94 => 660,
+            95 => 665,
+            96 => 670,
+            97 => 675,
+            98 => 680,
+            99 => 685,
+            100 => 690,
+            _ => return Err(CraftContextError::UnsupportedJobLevel(player_job_level)),
+        }))
     }
 }
 
 impl CraftContext {
     #[allow(clippy::cast_precision_loss)]
-    fn factors(player: &Player, recipe: &Recipe) -> (f32, f32) {
+    fn factors(player: &Player, recipe: &Recipe) -> Result<(f32, f32), CraftContextError> {
         // https://github.com/ffxiv-teamcraft/simulator/blob/72f4a6037baa3cd7cd78dfe34207283b824881a2/src/model/actions/crafting-action.ts#L176
 
         let progress_div = recipe.progress_div as f32;
@@ -86,17 +166,17 @@ impl CraftContext {
         let quality_div = recipe.quality_div as f32;
         let mut quality_factor: f32 = (player.control * 10) as f32 / quality_div + 35.0;
 
-        if let Some(base_recipe_level) = get_player_clvl(player.job_level) {
+        if let Some(base_recipe_level) = get_player_clvl(player.job_level)? {
             if base_recipe_level <= recipe.recipe_level {
                 progress_factor *= recipe.progress_mod as f32 / 100.0;
                 quality_factor *= recipe.quality_mod as f32 / 100.0;
             }
         }
 
-        (progress_factor.floor(), quality_factor.floor())
+        Ok((progress_factor.floor(), quality_factor.floor()))
     }
 
-    fn determine_action_pool(player: &Player, recipe: &Recipe) -> ActionSet {
+    fn determine_action_pool(player: &Player, recipe: &Recipe, options: CraftOptions) -> ActionSet {
         let mut pool = ActionSet::default();
 
         for action in Action::ACTIONS {
@@ -107,6 +187,9 @@ impl CraftContext {
                 {
                     continue;
                 }
+                if action.is_specialist_only() && !options.is_specialist {
+                    continue;
+                }
 
                 pool.insert(*action);
             }
@@ -123,14 +206,68 @@ impl CraftContext {
             if pool.contains(GroundworkTraited) && pool.contains(Groundwork) {
                 pool.remove(Groundwork);
             }
+            if pool.contains(RapidSynthesisTraited) && pool.contains(RapidSynthesis) {
+                pool.remove(RapidSynthesis);
+            }
+            if pool.contains(DelicateSynthesisTraited) && pool.contains(DelicateSynthesis) {
+                pool.remove(DelicateSynthesis);
+            }
         }
 
         pool
     }
 
+    fn validate(recipe: &Recipe, options: CraftOptions) -> Result<(), CraftContextError> {
+        if recipe.progress_div == 0 || recipe.quality_div == 0 {
+            return Err(CraftContextError::ZeroDivider);
+        }
+        if recipe.progress == 0 {
+            return Err(CraftContextError::ZeroProgress);
+        }
+        if recipe.durability <= 0 {
+            return Err(CraftContextError::InvalidDurability(recipe.durability));
+        }
+        if let Some(quality_target) = options.quality_target {
+            if quality_target > recipe.quality {
+                return Err(CraftContextError::QualityTargetTooHigh {
+                    quality_target,
+                    max_quality: recipe.quality,
+                });
+            }
+        }
+        if let Some(starting_quality) = options.starting_quality {
+            if starting_quality > recipe.quality {
+                return Err(CraftContextError::StartingQualityTooHigh {
+                    starting_quality,
+                    max_quality: recipe.quality,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the player or recipe is invalid. Use `try_new` to handle
+    /// invalid input instead.
     pub fn new(player: &Player, recipe: &Recipe, options: CraftOptions) -> Self {
-        let (progress_factor, quality_factor) = Self::factors(player, recipe);
-        Self {
+        Self::try_new(player, recipe, options).unwrap()
+    }
+
+    /// Creates a `CraftContext` without panicking on bad input.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CraftContextError` if the player's job level is unsupported,
+    /// or if the recipe or options can't be used for a craft.
+    pub fn try_new(
+        player: &Player,
+        recipe: &Recipe,
+        options: CraftOptions,
+    ) -> Result<Self, CraftContextError> {
+        Self::validate(recipe, options)?;
+        let (progress_factor, quality_factor) = Self::factors(player, recipe)?;
+        Ok(Self {
             player_job_level: player.job_level,
             recipe_job_level: recipe.job_level,
             progress_factor,
@@ -142,7 +279,119 @@ impl CraftContext {
             durability_max: recipe.durability,
             cp_max: player.cp,
             is_expert: recipe.is_expert,
-            action_pool: Self::determine_action_pool(player, recipe),
-        }
+            is_specialist: options.is_specialist,
+            conditions: Condition::from_flag(recipe.conditions_flag),
+            action_pool: Self::determine_action_pool(player, recipe, options),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CraftContext, CraftContextError, CraftOptions};
+    use crate::{
+        test_fixtures::{self, recipe},
+        Player, Recipe,
+    };
+
+    fn try_new(player: &Player, recipe: &Recipe) -> Result<CraftContext, CraftContextError> {
+        let options = CraftOptions {
+            max_steps: 25,
+            ..Default::default()
+        };
+        CraftContext::try_new(player, recipe, options)
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_inputs_through_json() {
+        let json = serde_json::to_string(&recipe()).unwrap();
+        assert_eq!(serde_json::from_str::<Recipe>(&json).unwrap(), recipe());
+
+        let player = test_fixtures::player();
+        let json = serde_json::to_string(&player).unwrap();
+        let restored: Player = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+        assert_eq!(restored.cp, 575);
+
+        // missing options fall back to defaults
+        let options: CraftOptions =
+            serde_json::from_str(r#"{"max_steps":30,"quality_target":5000}"#).unwrap();
+        assert_eq!(options.max_steps, 30);
+        assert_eq!(options.quality_target, Some(5000));
+        assert_eq!(options.starting_quality, None);
+        assert!(!options.is_specialist);
+
+        let options: CraftOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options.max_steps, 50);
+        let context = CraftContext::new(&player, &recipe(), options);
+        assert_eq!(context.step_max, 50);
+    }
+
+    #[test]
+    fn accepts_valid_input() {
+        let player = test_fixtures::player();
+        assert!(try_new(&player, &recipe()).is_ok());
+    }
+
+    #[test]
+    fn rejects_unsupported_levels() {
+        let player = Player {
+            job_level: 101,
+            ..test_fixtures::player()
+        };
+        assert_eq!(
+            try_new(&player, &recipe()).unwrap_err(),
+            CraftContextError::UnsupportedJobLevel(101)
+        );
+
+        let player = Player {
+            job_level: 0,
+            ..test_fixtures::player()
+        };
+        assert_eq!(
+            try_new(&player, &recipe()).unwrap_err(),
+            CraftContextError::UnsupportedJobLevel(0)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_recipes() {
+        let player = test_fixtures::player();
+
+        let zero_divider = Recipe {
+            progress_div: 0,
+            ..recipe()
+        };
+        assert_eq!(
+            try_new(&player, &zero_divider).unwrap_err(),
+            CraftContextError::ZeroDivider
+        );
+
+        let zero_durability = Recipe {
+            durability: 0,
+            ..recipe()
+        };
+        assert_eq!(
+            try_new(&player, &zero_durability).unwrap_err(),
+            CraftContextError::InvalidDurability(0)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_quality() {
+        let player = test_fixtures::player();
+        let options = CraftOptions {
+            max_steps: 25,
+            quality_target: Some(8000),
+            ..Default::default()
+        };
+        assert_eq!(
+            CraftContext::try_new(&player, &recipe(), options).unwrap_err(),
+            CraftContextError::QualityTargetTooHigh {
+                quality_target: 8000,
+                max_quality: 7200
+            }
+        );
     }
 }
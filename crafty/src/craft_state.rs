@@ -1,7 +1,8 @@
-use crate::{action::Attributes, Action, ActionSet, BitFlagExt, CraftContext};
+use crate::{action::Attributes, Action, ActionSet, BitFlagExt, Condition, CraftContext};
 use std::{cmp, fmt};
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CraftResult {
     /// The craft reached 100% progress. Includes the score of the `CraftState`.
     Finished(f32),
@@ -13,7 +14,8 @@ pub enum CraftResult {
     InvalidActionFailure,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Buffs {
     pub inner_quiet: u8,
     pub waste_not: u8,
@@ -24,6 +26,13 @@ pub struct Buffs {
     pub veneration: u8,
     pub makers_mark: u8,
     pub muscle_memory: u8,
+    pub final_appraisal: u8,
+    pub expedience: u8,
+    /// Lasts until the next action that consumes durability
+    pub trained_perfection: bool,
+    /// Lasts until a condition-restricted action is used outside of a Good or
+    /// Excellent condition
+    pub heart_and_soul: bool,
 }
 
 impl Buffs {
@@ -42,9 +51,40 @@ impl Buffs {
         self.veneration = self.veneration.saturating_sub(1);
         self.makers_mark = self.makers_mark.saturating_sub(1);
         self.muscle_memory = self.muscle_memory.saturating_sub(1);
+        self.final_appraisal = self.final_appraisal.saturating_sub(1);
+        self.expedience = self.expedience.saturating_sub(1);
     }
 }
 
+/// One of the possible random results of executing an action
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outcome {
+    /// Whether the action succeeded
+    pub success: bool,
+    /// The condition of the following step
+    pub condition: Condition,
+    pub probability: f32,
+}
+
+/// The fields of a `CraftState` that affect how the rest of the craft plays
+/// out. States with equal keys can share a node in the search tree, no matter
+/// which actions were used to reach them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct StateKey {
+    step: u8,
+    progress: u32,
+    quality: u32,
+    durability: i8,
+    cp: u32,
+    condition: Condition,
+    observe: bool,
+    next_combo_action: Option<Action>,
+    buffs: Buffs,
+    charges: [u8; 4],
+    /// Refined Touch gives an extra Inner Quiet stack after Basic Touch
+    after_basic_touch: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct CraftState<'a> {
     /// This is intended to be a readonly field that contains important values
@@ -57,11 +97,18 @@ pub struct CraftState<'a> {
     pub quality: u32,
     pub durability: i8,
     pub cp: u32,
+    pub condition: Condition,
 
     pub observe: bool,
     pub next_combo_action: Option<Action>,
     pub buffs: Buffs,
 
+    /// Remaining uses of actions that are limited to once per craft
+    pub quick_innovation_charges: u8,
+    pub trained_perfection_charges: u8,
+    pub careful_observation_charges: u8,
+    pub heart_and_soul_charges: u8,
+
     /// The action that led to this state
     pub action: Option<Action>,
     /// Sum of scores from this node onward
@@ -73,11 +120,33 @@ pub struct CraftState<'a> {
     pub available_moves: ActionSet,
 }
 
-impl<'a> fmt::Display for CraftState<'a> {
+/// The parts of a `CraftState` that change during a craft, without the
+/// context or search statistics. Restore it with `CraftState::from_snapshot`
+/// using the same context it was taken from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CraftStateSnapshot {
+    pub step: u8,
+    pub progress: u32,
+    pub quality: u32,
+    pub durability: i8,
+    pub cp: u32,
+    pub condition: Condition,
+    pub observe: bool,
+    pub next_combo_action: Option<Action>,
+    pub buffs: Buffs,
+    pub quick_innovation_charges: u8,
+    pub trained_perfection_charges: u8,
+    pub careful_observation_charges: u8,
+    pub heart_and_soul_charges: u8,
+    pub action: Option<Action>,
+}
+
+impl fmt::Display for CraftState<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{:>5}/{:>5} progress | {:>5}/{:>5} quality | {:>2}/{:>2} durability | {:>3}/{:>3} cp",
+            "{:>5}/{:>5} progress | {:>5}/{:>5} quality | {:>2}/{:>2} durability | {:>3}/{:>3} cp | {}",
             self.progress,
             self.context.progress_target,
             self.quality,
@@ -85,7 +154,8 @@ impl<'a> fmt::Display for CraftState<'a> {
             self.durability,
             self.context.durability_max,
             self.cp,
-            self.context.cp_max
+            self.context.cp_max,
+            self.condition
         )
     }
 }
@@ -99,9 +169,14 @@ impl<'a> CraftState<'a> {
             quality: context.starting_quality,
             durability: context.durability_max,
             cp: context.cp_max,
+            condition: Condition::Normal,
             observe: false,
             next_combo_action: None,
             buffs: Buffs::new(),
+            quick_innovation_charges: 1,
+            trained_perfection_charges: 1,
+            careful_observation_charges: 3,
+            heart_and_soul_charges: 1,
             action: None,
             score_sum: 0.0,
             max_score: 0.0,
@@ -112,27 +187,108 @@ impl<'a> CraftState<'a> {
 
     pub fn new(context: &'a CraftContext) -> Self {
         let mut state = Self::_new(context);
-        state.set_available_moves(false);
+        state.set_available_moves(false, true);
         state
     }
 
+    /// Rebuilds a state from a snapshot, with the same available moves as
+    /// `new`
+    pub fn from_snapshot(context: &'a CraftContext, snapshot: CraftStateSnapshot) -> Self {
+        let mut state = Self {
+            step: snapshot.step,
+            progress: snapshot.progress,
+            quality: snapshot.quality,
+            durability: snapshot.durability,
+            cp: snapshot.cp,
+            condition: snapshot.condition,
+            observe: snapshot.observe,
+            next_combo_action: snapshot.next_combo_action,
+            buffs: snapshot.buffs,
+            quick_innovation_charges: snapshot.quick_innovation_charges,
+            trained_perfection_charges: snapshot.trained_perfection_charges,
+            careful_observation_charges: snapshot.careful_observation_charges,
+            heart_and_soul_charges: snapshot.heart_and_soul_charges,
+            action: snapshot.action,
+            ..Self::_new(context)
+        };
+        state.set_available_moves(false, true);
+        state
+    }
+
+    pub fn snapshot(&self) -> CraftStateSnapshot {
+        CraftStateSnapshot {
+            step: self.step,
+            progress: self.progress,
+            quality: self.quality,
+            durability: self.durability,
+            cp: self.cp,
+            condition: self.condition,
+            observe: self.observe,
+            next_combo_action: self.next_combo_action,
+            buffs: self.buffs.clone(),
+            quick_innovation_charges: self.quick_innovation_charges,
+            trained_perfection_charges: self.trained_perfection_charges,
+            careful_observation_charges: self.careful_observation_charges,
+            heart_and_soul_charges: self.heart_and_soul_charges,
+            action: self.action,
+        }
+    }
+
     pub fn new_strict(context: &'a CraftContext) -> Self {
         let mut state = Self::_new(context);
-        state.set_available_moves(true);
+        state.set_available_moves(true, false);
         state
     }
 
     pub fn clone_strict(&self) -> Self {
         let mut state = self.clone();
-        state.set_available_moves(true);
+        state.set_available_moves(true, false);
+        state
+    }
+
+    /// Keeps every move allowed by the game except actions that can fail, for
+    /// searches that need to consider all deterministic rotations
+    pub fn clone_deterministic(&self) -> Self {
+        let mut state = self.clone();
+        state.set_available_moves(false, false);
+        state
+    }
+
+    /// Like `clone_strict`, but keeps actions that can fail available
+    pub fn clone_stochastic(&self) -> Self {
+        let mut state = self.clone();
+        state.set_available_moves(true, true);
         state
     }
 
+    pub(crate) fn key(&self) -> StateKey {
+        StateKey {
+            step: self.step,
+            progress: self.progress,
+            quality: self.quality,
+            durability: self.durability,
+            cp: self.cp,
+            condition: self.condition,
+            observe: self.observe,
+            next_combo_action: self.next_combo_action,
+            buffs: self.buffs.clone(),
+            charges: [
+                self.quick_innovation_charges,
+                self.trained_perfection_charges,
+                self.careful_observation_charges,
+                self.heart_and_soul_charges,
+            ],
+            after_basic_touch: self.action == Some(Action::BasicTouch),
+        }
+    }
+
     /// Examine the current craft state and populate `available_moves`.
     /// Enabling `strict` will add more rules that aim to prune as many
-    /// suboptimal moves as possible.
+    /// suboptimal moves as possible. Unless `allow_random` is enabled, strict
+    /// mode also prunes actions that can fail, since deterministic searches
+    /// assume every action succeeds.
     #[allow(clippy::too_many_lines)]
-    fn set_available_moves(&mut self, strict: bool) -> &mut Self {
+    fn set_available_moves(&mut self, strict: bool, allow_random: bool) -> &mut Self {
         if self.progress >= self.context.progress_target
             || self.step >= self.context.step_max
             || self.durability <= 0
@@ -140,7 +296,7 @@ impl<'a> CraftState<'a> {
             return self;
         }
 
-        let mut available_moves = self.context.action_pool.clone();
+        let mut available_moves = self.context.action_pool;
         available_moves.keep(|action| {
             use Action::*;
             let attrs = action.attributes();
@@ -156,6 +312,12 @@ impl<'a> CraftState<'a> {
                 return false;
             }
 
+            if let Some(base_rate) = attrs.success_rate {
+                if !allow_random && Action::calc_success_rate(self, base_rate) < 1.0 {
+                    return false;
+                }
+            }
+
             if strict {
                 // always used Trained Eye if it's available
                 if self.step == 1
@@ -166,8 +328,12 @@ impl<'a> CraftState<'a> {
                     return action == TrainedEye;
                 }
 
-                // only allow Focused moves after Observe
-                if self.observe && action != FocusedSynthesis && action != FocusedTouch {
+                // only allow Focused moves after Observe, or the Advanced Touch combo
+                if self.observe
+                    && action != FocusedSynthesis
+                    && action != FocusedTouch
+                    && !(action == AdvancedTouch && self.next_combo_action == Some(AdvancedTouch))
+                {
                     return false;
                 }
 
@@ -215,6 +381,31 @@ impl<'a> CraftState<'a> {
                 ByregotsBlessing if strict => self.buffs.inner_quiet > 1,
                 ByregotsBlessing => self.buffs.inner_quiet > 0,
                 TrainedFinesse => self.buffs.inner_quiet == 10,
+                // only usable in Good or Excellent conditions, or with Heart and Soul
+                TricksOfTheTrade | PreciseTouch | IntensiveSynthesis
+                    if !self.is_good_or_excellent() && !self.buffs.heart_and_soul =>
+                {
+                    false
+                }
+                // don't allow Tricks of the Trade to waste CP
+                TricksOfTheTrade if strict => self.context.cp_max - self.cp >= 20,
+                FinalAppraisal => self.buffs.final_appraisal == 0,
+                DaringTouch => self.buffs.expedience > 0,
+                QuickInnovation => self.quick_innovation_charges > 0 && self.buffs.innovation == 0,
+                TrainedPerfection => {
+                    self.trained_perfection_charges > 0 && !self.buffs.trained_perfection
+                }
+                // only use Refined Touch as a combo
+                RefinedTouch if strict => self.action == Some(BasicTouch),
+                ImmaculateMend if strict => self.context.durability_max - self.durability > 30,
+                // rerolling the condition is meaningless when conditions aren't random, and
+                // wasteful when the current condition is already good
+                CarefulObservation if !allow_random => false,
+                CarefulObservation if strict => {
+                    self.careful_observation_charges > 0 && !self.is_good_or_excellent()
+                }
+                CarefulObservation => self.careful_observation_charges > 0,
+                HeartAndSoul => self.heart_and_soul_charges > 0 && !self.buffs.heart_and_soul,
                 // use of Waste Not should be efficient
                 PrudentSynthesis | PrudentTouch | WasteNot | WasteNotII if strict => {
                     self.buffs.waste_not == 0 && self.buffs.waste_not_ii == 0
@@ -247,12 +438,21 @@ impl<'a> CraftState<'a> {
                 | CarefulSynthesis
                 | CarefulSynthesisTraited
                 | DelicateSynthesis
+                | DelicateSynthesisTraited
                 | GreatStrides
+                | HastyTouch
+                | ImmaculateMend
+                | IntensiveSynthesis
                 | Innovation
                 | Manipulation
                 | MastersMend
+                | PreciseTouch
                 | PreparatoryTouch
+                | RapidSynthesis
+                | RapidSynthesisTraited
+                | RefinedTouch
                 | StandardTouch
+                | TricksOfTheTrade
                 | Veneration
                 | WasteNot
                 | WasteNotII => true,
@@ -265,7 +465,7 @@ impl<'a> CraftState<'a> {
 
     // interesting lint, but passing by value apparently results in a 2-3% performance regression?
     #[allow(clippy::trivially_copy_pass_by_ref)]
-    fn _execute(&self, action: &Action) -> Self {
+    fn _execute(&self, action: &Action, success: bool) -> Self {
         let mut state = Self {
             step: self.step + 1,
             buffs: self.buffs.clone(),
@@ -283,21 +483,30 @@ impl<'a> CraftState<'a> {
             quality_efficiency,
             durability_cost,
             cp_cost,
+            success_rate: _,
             effect,
         } = action.attributes();
 
-        if let Some(efficiency) = progress_efficiency {
+        if let Some(efficiency) = progress_efficiency.filter(|_| success) {
             state.progress += Action::calc_progress_increase(&state, efficiency);
             state.buffs.muscle_memory = 0;
+
+            if state.buffs.final_appraisal > 0 && state.progress >= state.context.progress_target {
+                state.progress = state.context.progress_target - 1;
+                state.buffs.final_appraisal = 0;
+            }
         }
 
-        if let Some(efficiency) = quality_efficiency {
+        if let Some(efficiency) = quality_efficiency.filter(|_| success) {
             state.quality += Action::calc_quality_increase(&state, efficiency);
 
             if state.context.player_job_level >= 11 {
                 state.buffs.inner_quiet = match &action {
                     Action::ByregotsBlessing => 0,
-                    Action::Reflect | Action::PreparatoryTouch => {
+                    Action::Reflect | Action::PreparatoryTouch | Action::PreciseTouch => {
+                        cmp::min(state.buffs.inner_quiet + 2, 10)
+                    }
+                    Action::RefinedTouch if self.action == Some(Action::BasicTouch) => {
                         cmp::min(state.buffs.inner_quiet + 2, 10)
                     }
                     _ => cmp::min(state.buffs.inner_quiet + 1, 10),
@@ -309,9 +518,31 @@ impl<'a> CraftState<'a> {
 
         if let Some(base_cost) = durability_cost {
             state.durability -= Action::calc_durability_cost(&state, base_cost);
+            if base_cost > 0 {
+                state.buffs.trained_perfection = false;
+            }
         }
 
-        if state.buffs.manipulation > 0 && state.durability > 0 {
+        // Heart and Soul is only consumed if it was needed
+        if matches!(
+            action,
+            Action::TricksOfTheTrade | Action::PreciseTouch | Action::IntensiveSynthesis
+        ) && !state.is_good_or_excellent()
+        {
+            state.buffs.heart_and_soul = false;
+        }
+
+        let ticks_buffs = !action.skips_buff_ticks();
+
+        // starting at level 96, Manipulation doesn't restore durability on the
+        // step it's refreshed
+        let restores_durability =
+            *action != Action::Manipulation || state.context.player_job_level < 96;
+        if ticks_buffs
+            && restores_durability
+            && state.buffs.manipulation > 0
+            && state.durability > 0
+        {
             state.durability = cmp::min(state.durability + 5, state.context.durability_max);
         }
 
@@ -325,32 +556,94 @@ impl<'a> CraftState<'a> {
             state.next_combo_action = None;
         }
 
-        state.buffs.decrement_timers();
+        if ticks_buffs {
+            state.buffs.decrement_timers();
+        }
 
         // Always apply effects last
-        if let Some(apply_effect) = effect {
+        if let Some(apply_effect) = effect.filter(|_| success) {
             apply_effect(&mut state);
         }
 
+        // The next condition is random unless it's forced by the current one;
+        // assume Normal here and let the caller roll a different condition
+        if action.changes_condition() {
+            state.condition = state.condition.forced_next().unwrap_or(Condition::Normal);
+        }
+
         state
     }
 
     /// Executes the action against a `CraftState`, and returns a `CraftState` with
     /// all available moves
     pub fn execute(&self, action: &Action) -> Self {
-        let mut state = self._execute(action);
-        state.set_available_moves(false);
+        let mut state = self._execute(action, true);
+        state.set_available_moves(false, true);
         state
     }
 
     /// Executes the action against a `CraftState`, and returns a `CraftState` with
     /// a strict, pruned moveset
     pub fn execute_strict(&self, action: &Action) -> Self {
-        let mut state = self._execute(action);
-        state.set_available_moves(true);
+        let mut state = self._execute(action, true);
+        state.set_available_moves(true, false);
         state
     }
 
+    /// Executes the action against a `CraftState`, and returns a `CraftState`
+    /// with every move that can't fail
+    pub fn execute_deterministic(&self, action: &Action) -> Self {
+        let mut state = self._execute(action, true);
+        state.set_available_moves(false, false);
+        state
+    }
+
+    /// Executes the action against a `CraftState` with a specific random
+    /// outcome, and returns a `CraftState` with a strict, pruned moveset that
+    /// still includes actions that can fail
+    pub fn execute_stochastic(&self, action: &Action, outcome: &Outcome) -> Self {
+        let mut state = self._execute(action, outcome.success);
+        state.condition = outcome.condition;
+        state.set_available_moves(true, true);
+        state
+    }
+
+    fn is_good_or_excellent(&self) -> bool {
+        matches!(self.condition, Condition::Good | Condition::Excellent)
+    }
+
+    /// Every random outcome of executing the action, along with their
+    /// probabilities
+    pub fn outcomes(&self, action: &Action) -> Vec<Outcome> {
+        let successes = match action.attributes().success_rate {
+            Some(base_rate) => {
+                let rate = Action::calc_success_rate(self, base_rate);
+                vec![(true, rate), (false, 1.0 - rate)]
+            }
+            None => vec![(true, 1.0)],
+        };
+        let conditions = if action.changes_condition() {
+            self.condition.next_conditions(self.context)
+        } else {
+            vec![(self.condition, 1.0)]
+        };
+
+        let mut outcomes = vec![];
+        for (success, success_probability) in successes {
+            if success_probability <= 0.0 {
+                continue;
+            }
+            for (condition, condition_probability) in &conditions {
+                outcomes.push(Outcome {
+                    success,
+                    condition: *condition,
+                    probability: success_probability * condition_probability,
+                });
+            }
+        }
+        outcomes
+    }
+
     /// An evaluation of the craft. Returns a value from 0 to 1.
     #[allow(clippy::cast_precision_loss)]
     pub fn score(&self) -> f32 {
@@ -426,3 +719,48 @@ impl<'a> CraftState<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_fixtures, Simulator};
+
+    fn simulated_state(context: &CraftContext) -> CraftState<'_> {
+        use Action::*;
+        let actions = vec![Reflect, Manipulation, Veneration, BasicTouch];
+        Simulator::simulate(context, actions).0
+    }
+
+    #[test]
+    fn restores_snapshot() {
+        let context = test_fixtures::context();
+        let state = simulated_state(&context);
+        let restored = CraftState::from_snapshot(&context, state.snapshot());
+
+        assert_eq!(restored.snapshot(), state.snapshot());
+        assert_eq!(restored.buffs.manipulation, state.buffs.manipulation);
+        assert_eq!(restored.next_combo_action, Some(Action::StandardTouch));
+        assert_eq!(restored.available_moves, state.available_moves);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_snapshot_through_json() {
+        let context = test_fixtures::context();
+        let snapshot = simulated_state(&context).snapshot();
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(json.contains("\"next_combo_action\":\"StandardTouch\""));
+        assert!(json.contains("\"action\":\"BasicTouch\""));
+        let restored: CraftStateSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, snapshot);
+
+        let result: CraftResult =
+            serde_json::from_str(&serde_json::to_string(&CraftResult::Finished(0.5)).unwrap())
+                .unwrap();
+        assert!(
+            matches!(result, CraftResult::Finished(score) if (score - 0.5).abs() < f32::EPSILON)
+        );
+        assert!(serde_json::from_str::<Action>("\"Basic Synthesis\"").is_err());
+    }
+}
@@ -0,0 +1,408 @@
+use crate::{craft_state::StateKey, Action, CraftContext, CraftResult, CraftState, Simulator};
+use std::{
+    cmp::{self, Reverse},
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExactOptions {
+    /// Stops the search after this much time has passed, returning the best
+    /// rotation found so far. Searches until done if None
+    pub time_limit: Option<Duration>,
+    /// Maximum number of states to remember. States past this limit are
+    /// searched again every time they're reached. Unlimited if None
+    pub max_memo_entries: Option<usize>,
+}
+
+impl Default for ExactOptions {
+    fn default() -> Self {
+        Self {
+            time_limit: None,
+            max_memo_entries: Some(5_000_000),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExactSolution<'a> {
+    pub actions: Vec<Action>,
+    pub state: CraftState<'a>,
+    /// Whether the search ran to completion, which guarantees that no other
+    /// rotation reaches more quality, or the same quality in fewer steps
+    pub optimal: bool,
+    /// Number of states that were expanded during the search
+    pub states_searched: u64,
+}
+
+/// Final quality (capped at the quality target), then fewest steps. None if
+/// the craft can't be finished.
+type Value = Option<(u32, Reverse<u8>)>;
+
+enum Entry {
+    Exact(Value, Option<Action>),
+    /// The state was searched with a lower bound that it couldn't beat, so
+    /// only an upper bound on its value is known
+    UpperBound(Value),
+}
+
+/// Finds the best rotation for a craft without random conditions by searching
+/// every deterministic rotation. States are memoized by their gameplay fields,
+/// and branches that can't beat the best rotation found so far are pruned with
+/// an optimistic bound on the quality they could still gain.
+pub struct ExactSearch<'a> {
+    context: &'a CraftContext,
+    options: ExactOptions,
+    memo: HashMap<StateKey, Entry>,
+    started: Instant,
+    timed_out: bool,
+    states_searched: u64,
+
+    quality_bound: QualityBound,
+
+    path: Vec<Action>,
+    best_value: Value,
+    best_path: Vec<Action>,
+}
+
+/// Optimistic limits on the quality a craft can still gain, assuming every
+/// action that can be afforded adds as much quality as possible
+#[derive(Debug, Clone)]
+pub(crate) struct QualityBound {
+    /// The most quality a single action could possibly add
+    max_quality_per_action: u32,
+    /// The cheapest quality actions, including and excluding actions that can
+    /// only be used on the first step
+    min_quality_cp_cost: (u32, u32),
+}
+
+impl QualityBound {
+    pub(crate) fn new(context: &CraftContext) -> Self {
+        let quality_actions: Vec<(Action, f32, u32)> = context
+            .action_pool
+            .iter()
+            .filter_map(|action| {
+                let attrs = action.attributes();
+                let efficiency = match action {
+                    // up to 10 stacks of Inner Quiet at 20% each
+                    Action::ByregotsBlessing => Some(3.0),
+                    _ => attrs.quality_efficiency,
+                };
+                efficiency
+                    .filter(|_| attrs.success_rate.is_none())
+                    .map(|efficiency| (action, efficiency, attrs.cp_cost.unwrap_or(0)))
+            })
+            .collect();
+
+        let max_efficiency = quality_actions
+            .iter()
+            .map(|(_, efficiency, _)| *efficiency)
+            .fold(0.0, f32::max);
+        // up to 10 stacks of Inner Quiet, Innovation, and Great Strides
+        let max_modifier = 2.0 * 2.5;
+
+        let min_cost = |first_step: bool| {
+            quality_actions
+                .iter()
+                .filter(|(action, _, _)| {
+                    first_step || !matches!(action, Action::Reflect | Action::TrainedEye)
+                })
+                .map(|(_, _, cp_cost)| *cp_cost)
+                .min()
+                .unwrap_or(0)
+        };
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let max_quality_per_action =
+            (context.quality_factor * max_efficiency * max_modifier).ceil() as u32;
+
+        Self {
+            max_quality_per_action,
+            min_quality_cp_cost: (min_cost(true), min_cost(false)),
+        }
+    }
+
+    /// The most quality `state` could have after `actions` more actions,
+    /// capped at the quality target
+    pub(crate) fn max_quality(&self, state: &CraftState, actions: u32) -> u32 {
+        let target = state.context.quality_target;
+        if state.step == 1 && state.context.action_pool.contains(Action::TrainedEye) {
+            return target;
+        }
+
+        let min_cp_cost = if state.step == 1 {
+            self.min_quality_cp_cost.0
+        } else {
+            self.min_quality_cp_cost.1
+        };
+        // Heart and Soul allows one Tricks of the Trade without a Good condition
+        let can_use_tricks = state.context.action_pool.contains(Action::TricksOfTheTrade)
+            && (state.heart_and_soul_charges > 0 || state.buffs.heart_and_soul);
+        let cp = state.cp + if can_use_tricks { 20 } else { 0 };
+        let affordable_actions = cp
+            .checked_div(min_cp_cost)
+            .map_or(actions, |affordable| cmp::min(actions, affordable));
+
+        let quality = state.quality + affordable_actions * self.max_quality_per_action;
+        cmp::min(quality, target)
+    }
+
+    /// The fewest steps a craft from `state` could end on while finishing at
+    /// the quality target, since at least one more action is needed to finish
+    /// it. None if the target can't be reached before the step limit.
+    pub(crate) fn min_target_step(&self, state: &CraftState) -> Option<u8> {
+        let target = state.context.quality_target;
+        let remaining_actions = state.context.step_max.saturating_sub(state.step);
+        (1..=remaining_actions)
+            .find(|&actions| self.max_quality(state, u32::from(actions)) >= target)
+            .map(|actions| state.step + actions)
+    }
+}
+
+impl<'a> ExactSearch<'a> {
+    fn new(context: &'a CraftContext, options: ExactOptions) -> Self {
+        Self {
+            context,
+            options,
+            memo: HashMap::new(),
+            started: Instant::now(),
+            timed_out: false,
+            states_searched: 0,
+            quality_bound: QualityBound::new(context),
+            path: vec![],
+            best_value: None,
+            best_path: vec![],
+        }
+    }
+
+    fn terminal_value(&self, state: &CraftState, result: &CraftResult) -> Value {
+        match result {
+            CraftResult::Finished(_) => Some((
+                cmp::min(state.quality, self.context.quality_target),
+                Reverse(state.step),
+            )),
+            _ => None,
+        }
+    }
+
+    /// An optimistic value for a state that hasn't ended: every remaining
+    /// action that can be afforded adds as much quality as possible, and the
+    /// craft finishes on the next step.
+    fn upper_bound(&self, state: &CraftState) -> (u32, Reverse<u8>) {
+        let remaining_actions = u32::from(self.context.step_max.saturating_sub(state.step));
+        let quality = self.quality_bound.max_quality(state, remaining_actions);
+        (quality, Reverse(state.step + 1))
+    }
+
+    fn out_of_time(&mut self) -> bool {
+        if !self.timed_out && self.states_searched.is_multiple_of(1024) {
+            if let Some(time_limit) = self.options.time_limit {
+                self.timed_out = self.started.elapsed() >= time_limit;
+            }
+        }
+        self.timed_out
+    }
+
+    /// Returns the value of a state, along with the first action of the best
+    /// rotation from it. If the state can't beat `alpha`, the search can stop
+    /// early and the returned value is only an upper bound.
+    fn search(&mut self, state: &CraftState<'a>, alpha: Value) -> (Value, Option<Action>) {
+        if let Some(result) = state.check_result() {
+            let value = self.terminal_value(state, &result);
+            if value > self.best_value {
+                self.best_value = value;
+                self.best_path.clone_from(&self.path);
+            }
+            return (value, None);
+        }
+
+        if self.out_of_time() {
+            return (None, None);
+        }
+        self.states_searched += 1;
+
+        let key = state.key();
+        match self.memo.get(&key) {
+            Some(Entry::Exact(value, action)) => return (*value, *action),
+            Some(Entry::UpperBound(bound)) if *bound <= alpha => return (*bound, None),
+            _ => {}
+        }
+
+        let bound = Some(self.upper_bound(state));
+        if bound <= alpha {
+            return (bound, None);
+        }
+
+        let mut best: Value = None;
+        let mut best_action = None;
+        for action in state.available_moves {
+            let next_state = state.execute_deterministic(&action);
+            self.path.push(action);
+            let (value, _) = self.search(&next_state, cmp::max(alpha, best));
+            self.path.pop();
+
+            if value > best {
+                best = value;
+                best_action = Some(action);
+            }
+            if best >= bound || self.timed_out {
+                break;
+            }
+        }
+
+        if self.timed_out {
+            return (best, best_action);
+        }
+
+        let has_room = self
+            .options
+            .max_memo_entries
+            .is_none_or(|max| self.memo.len() < max);
+        if has_room || self.memo.contains_key(&key) {
+            let entry = if best > alpha {
+                Entry::Exact(best, best_action)
+            } else {
+                Entry::UpperBound(best)
+            };
+            self.memo.insert(key, entry);
+        }
+
+        (best, best_action)
+    }
+
+    /// Searches for the rotation with the most quality, then the fewest steps,
+    /// after `action_history`. Actions that can fail are never used. If the
+    /// time limit is reached, the best rotation found so far is returned
+    /// instead; if no rotation finishes the craft, the actions and state after
+    /// `action_history` are returned.
+    pub fn solve(
+        context: &'a CraftContext,
+        action_history: Vec<Action>,
+        options: ExactOptions,
+    ) -> ExactSolution<'a> {
+        let (start_state, result) = Simulator::simulate(context, action_history.clone());
+        if result.is_some() {
+            return ExactSolution {
+                actions: action_history,
+                state: start_state,
+                optimal: true,
+                states_searched: 0,
+            };
+        }
+
+        let start_state = start_state.clone_deterministic();
+        let mut solver = Self::new(context, options);
+        solver.search(&start_state, None);
+
+        // follow the best action from each state, which is already memoized
+        // unless the memo is full
+        let optimal = !solver.timed_out;
+        let rotation = if optimal && solver.best_value.is_some() {
+            let mut rotation = vec![];
+            let mut state = start_state.clone();
+            while let (_, Some(action)) = solver.search(&state, None) {
+                rotation.push(action);
+                state = state.execute_deterministic(&action);
+            }
+            rotation
+        } else {
+            solver.best_path.clone()
+        };
+
+        let mut state = start_state;
+        for action in &rotation {
+            state = state.execute_deterministic(action);
+        }
+
+        ExactSolution {
+            actions: [action_history, rotation].concat(),
+            state,
+            optimal,
+            states_searched: solver.states_searched,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::low_level_context;
+
+    /// Value of the best rotation, found by trying every rotation
+    fn brute_force(context: &CraftContext, state: &CraftState) -> Value {
+        if let Some(result) = state.check_result() {
+            return match result {
+                CraftResult::Finished(_) => Some((
+                    cmp::min(state.quality, context.quality_target),
+                    Reverse(state.step),
+                )),
+                _ => None,
+            };
+        }
+        state
+            .available_moves
+            .iter()
+            .map(|action| brute_force(context, &state.execute_deterministic(&action)))
+            .max()
+            .flatten()
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let context = low_level_context(6);
+        let solution = ExactSearch::solve(&context, vec![], ExactOptions::default());
+        assert!(solution.optimal);
+
+        let expected = brute_force(&context, &CraftState::new(&context).clone_deterministic());
+        let (quality, Reverse(step)) = expected.unwrap();
+        assert_eq!(
+            cmp::min(solution.state.quality, context.quality_target),
+            quality
+        );
+        assert_eq!(solution.state.step, step);
+
+        let (state, _) = Simulator::simulate(&context, solution.actions);
+        assert_eq!(state.quality, solution.state.quality);
+        assert!(state.progress >= context.progress_target);
+    }
+
+    #[test]
+    fn continues_from_action_history() {
+        let context = low_level_context(6);
+        let solution = ExactSearch::solve(
+            &context,
+            vec![Action::Veneration, Action::BasicSynthesis],
+            ExactOptions::default(),
+        );
+        assert_eq!(
+            solution.actions[..2],
+            [Action::Veneration, Action::BasicSynthesis]
+        );
+        assert!(solution.state.progress >= context.progress_target);
+    }
+
+    #[test]
+    fn solves_without_memo() {
+        let context = low_level_context(5);
+        let options = ExactOptions {
+            max_memo_entries: Some(0),
+            ..Default::default()
+        };
+        let without_memo = ExactSearch::solve(&context, vec![], options);
+        let with_memo = ExactSearch::solve(&context, vec![], ExactOptions::default());
+        assert!(without_memo.optimal);
+        assert_eq!(without_memo.state.quality, with_memo.state.quality);
+        assert_eq!(without_memo.state.step, with_memo.state.step);
+    }
+
+    #[test]
+    fn stops_at_time_limit() {
+        let context = low_level_context(12);
+        let options = ExactOptions {
+            time_limit: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let solution = ExactSearch::solve(&context, vec![], options);
+        assert!(!solution.optimal);
+    }
+}
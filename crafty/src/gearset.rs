@@ -0,0 +1,466 @@
+use crate::{Action, CraftContext, CraftOptions, Player, Recipe, SearchOptions, Simulator};
+use std::{
+    cmp::{self, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+    error, fmt,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Stats {
+    pub craftsmanship: u32,
+    pub control: u32,
+    pub cp: u32,
+}
+
+impl Stats {
+    pub fn new(craftsmanship: u32, control: u32, cp: u32) -> Self {
+        Self {
+            craftsmanship,
+            control,
+            cp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MateriaStat {
+    Craftsmanship,
+    Control,
+    Cp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Materia {
+    pub stat: MateriaStat,
+    pub value: u32,
+}
+
+impl Materia {
+    pub fn new(stat: MateriaStat, value: u32) -> Self {
+        Self { stat, value }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GearItem {
+    pub stats: Stats,
+    /// The maximum value of each stat on this item. Materia can't raise a stat
+    /// past its cap.
+    pub caps: Stats,
+    /// Number of guaranteed materia slots
+    pub materia_slots: usize,
+    /// Number of additional slots that can be overmelded
+    pub overmeld_slots: usize,
+    pub materia: Vec<Materia>,
+}
+
+impl GearItem {
+    pub fn new(stats: Stats, caps: Stats, materia_slots: usize, overmeld_slots: usize) -> Self {
+        Self {
+            stats,
+            caps,
+            materia_slots,
+            overmeld_slots,
+            materia: vec![],
+        }
+    }
+
+    /// Stats of the item including melded materia, limited by the item's caps
+    pub fn total_stats(&self) -> Stats {
+        let melded = |stat: MateriaStat, base: u32, cap: u32| {
+            let materia_sum: u32 = self
+                .materia
+                .iter()
+                .filter(|materia| materia.stat == stat)
+                .map(|materia| materia.value)
+                .sum();
+            cmp::max(base, cmp::min(base + materia_sum, cap))
+        };
+
+        Stats {
+            craftsmanship: melded(
+                MateriaStat::Craftsmanship,
+                self.stats.craftsmanship,
+                self.caps.craftsmanship,
+            ),
+            control: melded(MateriaStat::Control, self.stats.control, self.caps.control),
+            cp: melded(MateriaStat::Cp, self.stats.cp, self.caps.cp),
+        }
+    }
+
+    fn free_slots(&self) -> usize {
+        (self.materia_slots + self.overmeld_slots).saturating_sub(self.materia.len())
+    }
+
+    /// The cost of melding materia into free slots. Guaranteed slots go to the
+    /// materia that cost the most extra to overmeld. Returns None if there
+    /// aren't enough free slots.
+    fn meld_cost(&self, materia: &[MateriaOption]) -> Option<u32> {
+        if materia.len() > self.free_slots() {
+            return None;
+        }
+
+        let guaranteed_slots = self.materia_slots.saturating_sub(self.materia.len());
+        let mut materia = materia.to_vec();
+        materia.sort_by_key(|option| Reverse(option.overmeld_cost.saturating_sub(option.cost)));
+        let cost = materia
+            .iter()
+            .enumerate()
+            .map(|(slot, option)| {
+                if slot < guaranteed_slots {
+                    option.cost
+                } else {
+                    option.overmeld_cost
+                }
+            })
+            .sum();
+        Some(cost)
+    }
+
+    /// Every way of melding materia into free slots, keeping only the cheapest
+    /// way to reach each total, sorted by cost
+    fn meld_choices(&self, materia: &[MateriaOption]) -> Vec<ItemMelds> {
+        fn visit(
+            item: &GearItem,
+            materia: &[MateriaOption],
+            combination: &mut Vec<MateriaOption>,
+            choices: &mut HashMap<Stats, ItemMelds>,
+        ) {
+            let Some(cost) = item.meld_cost(combination) else {
+                return;
+            };
+            let mut melded = item.clone();
+            melded
+                .materia
+                .extend(combination.iter().map(|option| option.materia));
+            let stats = melded.total_stats();
+            if choices.get(&stats).is_none_or(|choice| cost < choice.cost) {
+                let materia = combination.iter().map(|option| option.materia).collect();
+                choices.insert(stats, ItemMelds { materia, cost });
+            }
+
+            // options are only added in order so each combination is visited
+            // once
+            for (index, option) in materia.iter().enumerate() {
+                combination.push(*option);
+                visit(item, &materia[index..], combination, choices);
+                combination.pop();
+            }
+        }
+
+        let mut choices = HashMap::new();
+        visit(self, materia, &mut vec![], &mut choices);
+        let mut choices: Vec<(Stats, ItemMelds)> = choices.into_iter().collect();
+        choices.sort_by_key(|(stats, choice)| {
+            (
+                choice.cost,
+                choice.materia.len(),
+                stats.craftsmanship,
+                stats.control,
+                stats.cp,
+            )
+        });
+        choices.into_iter().map(|(_, choice)| choice).collect()
+    }
+}
+
+/// Materia to meld into one item, and what they cost
+#[derive(Debug, Clone)]
+struct ItemMelds {
+    materia: Vec<Materia>,
+    cost: u32,
+}
+
+/// A materia that the meld optimizer is allowed to use, and what it costs
+/// depending on whether it goes into a guaranteed or an overmeld slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MateriaOption {
+    pub materia: Materia,
+    pub cost: u32,
+    pub overmeld_cost: u32,
+}
+
+/// How many combinations of melds `Gearset::optimize_melds` visits before
+/// giving up
+pub const MAX_MELD_CANDIDATES: usize = 10_000;
+
+/// The cheapest set of melds found by `Gearset::optimize_melds`
+#[derive(Debug, Clone)]
+pub struct MeldSolution {
+    pub gearset: Gearset,
+    pub cost: u32,
+    pub actions: Vec<Action>,
+}
+
+/// Reasons `Gearset::optimize_melds` didn't find a set of melds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeldError {
+    /// No combination of melds reaches the quality target
+    NoSolution,
+    /// More than `MAX_MELD_CANDIDATES` combinations were visited without
+    /// reaching the quality target
+    TooManyCandidates,
+}
+
+impl fmt::Display for MeldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoSolution => write!(f, "no combination of melds reaches the quality target"),
+            Self::TooManyCandidates => write!(
+                f,
+                "no combination of melds reached the quality target within {MAX_MELD_CANDIDATES} candidates"
+            ),
+        }
+    }
+}
+
+impl error::Error for MeldError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gearset {
+    pub job_level: u32,
+    pub items: Vec<GearItem>,
+    /// The soul crystal grants specialist actions, along with a bonus of 20
+    /// craftsmanship, 20 control, and 15 CP
+    pub soul_crystal: bool,
+}
+
+impl Gearset {
+    pub fn new(job_level: u32, items: Vec<GearItem>, soul_crystal: bool) -> Self {
+        Self {
+            job_level,
+            items,
+            soul_crystal,
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats =
+            self.items
+                .iter()
+                .map(GearItem::total_stats)
+                .fold(Stats::default(), |acc, item| Stats {
+                    craftsmanship: acc.craftsmanship + item.craftsmanship,
+                    control: acc.control + item.control,
+                    cp: acc.cp + item.cp,
+                });
+
+        if self.soul_crystal {
+            stats.craftsmanship += 20;
+            stats.control += 20;
+            stats.cp += 15;
+        }
+
+        stats
+    }
+
+    pub fn player(&self) -> Player {
+        let stats = self.stats();
+        Player::new(self.job_level, stats.craftsmanship, stats.control, stats.cp)
+    }
+
+    /// Melds the choice at each index of `choices` into the matching item
+    fn meld(&self, choices: &[Vec<ItemMelds>], indexes: &[usize]) -> Gearset {
+        let mut gearset = self.clone();
+        for ((item, choices), &index) in gearset.items.iter_mut().zip(choices).zip(indexes) {
+            item.materia.extend_from_slice(&choices[index].materia);
+        }
+        gearset
+    }
+
+    /// Searches for the cheapest combination of materia that lets the solver
+    /// reach the recipe's quality target. Combinations of each item's meld
+    /// choices are visited in order of cost, and each new combination of
+    /// progress factor, quality factor, and CP is checked with
+    /// `Simulator::search_stepwise`, so the result is only as reliable as the
+    /// search.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no combination reaches the quality target, or if
+    /// more than `MAX_MELD_CANDIDATES` combinations were visited first.
+    pub fn optimize_melds(
+        &self,
+        recipe: &Recipe,
+        craft_options: CraftOptions,
+        search_options: &SearchOptions,
+        materia: &[MateriaOption],
+    ) -> Result<MeldSolution, MeldError> {
+        let choices: Vec<Vec<ItemMelds>> = self
+            .items
+            .iter()
+            .map(|item| item.meld_choices(materia))
+            .collect();
+
+        let craft_options = CraftOptions {
+            is_specialist: craft_options.is_specialist || self.soul_crystal,
+            ..craft_options
+        };
+
+        // the first choice of each item is to leave it as is, for free
+        let mut queue = BinaryHeap::from([Reverse((0, vec![0; choices.len()], 0))]);
+        let mut checked_factors = HashSet::new();
+        let mut candidates = 0;
+        while let Some(Reverse((cost, indexes, last_position))) = queue.pop() {
+            candidates += 1;
+            if candidates > MAX_MELD_CANDIDATES {
+                return Err(MeldError::TooManyCandidates);
+            }
+
+            // only moving to pricier choices at or after the last position
+            // that was moved means each combination is queued exactly once
+            for position in last_position..indexes.len() {
+                let index = indexes[position];
+                if let Some(next_choice) = choices[position].get(index + 1) {
+                    let mut next_indexes = indexes.clone();
+                    next_indexes[position] += 1;
+                    let next_cost = cost - choices[position][index].cost + next_choice.cost;
+                    queue.push(Reverse((next_cost, next_indexes, position)));
+                }
+            }
+
+            let gearset = self.meld(&choices, &indexes);
+            let Ok(context) = CraftContext::try_new(&gearset.player(), recipe, craft_options)
+            else {
+                continue;
+            };
+            // different stats often round to the same factors, which craft the
+            // same way. The factors are whole numbers, so their bits can be
+            // compared.
+            let factors = (
+                context.progress_factor.to_bits(),
+                context.quality_factor.to_bits(),
+                context.cp_max,
+            );
+            if !checked_factors.insert(factors) {
+                continue;
+            }
+            let (actions, state) =
+                Simulator::search_stepwise(&context, vec![], search_options.clone(), None);
+            if state.progress >= context.progress_target && state.quality >= context.quality_target
+            {
+                return Ok(MeldSolution {
+                    gearset,
+                    cost,
+                    actions,
+                });
+            }
+        }
+
+        Err(MeldError::NoSolution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::recipe;
+
+    fn item(craftsmanship: u32, control: u32, cp: u32) -> GearItem {
+        GearItem::new(
+            Stats::new(craftsmanship, control, cp),
+            Stats::new(craftsmanship + 100, control + 100, cp + 20),
+            2,
+            3,
+        )
+    }
+
+    #[test]
+    fn sums_item_stats() {
+        let gearset = Gearset::new(90, vec![item(1000, 800, 0), item(500, 600, 300)], true);
+        assert_eq!(gearset.stats(), Stats::new(1520, 1420, 315));
+        let player = gearset.player();
+        assert_eq!(player.job_level, 90);
+        assert_eq!(player.craftsmanship, 1520);
+    }
+
+    #[test]
+    fn materia_is_capped() {
+        let mut item = item(1000, 800, 0);
+        item.materia = vec![
+            Materia::new(MateriaStat::Control, 36),
+            Materia::new(MateriaStat::Control, 36),
+            Materia::new(MateriaStat::Control, 36),
+            Materia::new(MateriaStat::Cp, 11),
+        ];
+        assert_eq!(item.total_stats(), Stats::new(1000, 900, 11));
+    }
+
+    #[test]
+    fn melds_guaranteed_slots_first() {
+        let item = item(1000, 800, 0);
+        let option = MateriaOption {
+            materia: Materia::new(MateriaStat::Control, 36),
+            cost: 1,
+            overmeld_cost: 10,
+        };
+        assert_eq!(item.meld_cost(&[option; 2]), Some(2));
+        assert_eq!(item.meld_cost(&[option; 3]), Some(12));
+        assert_eq!(item.meld_cost(&[option; 6]), None);
+
+        // the materia that's pricier to overmeld takes the guaranteed slot
+        let cheap_overmeld = MateriaOption {
+            materia: Materia::new(MateriaStat::Cp, 11),
+            cost: 1,
+            overmeld_cost: 2,
+        };
+        assert_eq!(
+            item.meld_cost(&[cheap_overmeld, cheap_overmeld, option]),
+            Some(1 + 1 + 2)
+        );
+    }
+
+    #[test]
+    fn keeps_cheapest_meld_choice_for_each_total() {
+        let item = item(1000, 800, 0);
+        let option = MateriaOption {
+            materia: Materia::new(MateriaStat::Control, 36),
+            cost: 1,
+            overmeld_cost: 10,
+        };
+
+        // the cap of 900 control is reached with 3 materia
+        let choices = item.meld_choices(&[option]);
+        let costs: Vec<u32> = choices.iter().map(|choice| choice.cost).collect();
+        assert_eq!(costs, vec![0, 1, 2, 12]);
+        assert!(choices[0].materia.is_empty());
+    }
+
+    #[test]
+    fn optimizes_melds() {
+        let gearset = Gearset::new(
+            90,
+            vec![item(1700, 1700, 300), item(1600, 1620, 275)],
+            false,
+        );
+        let materia = [
+            MateriaOption {
+                materia: Materia::new(MateriaStat::Control, 36),
+                cost: 1,
+                overmeld_cost: 5,
+            },
+            MateriaOption {
+                materia: Materia::new(MateriaStat::Cp, 11),
+                cost: 1,
+                overmeld_cost: 5,
+            },
+        ];
+        let craft_options = CraftOptions {
+            max_steps: 25,
+            ..Default::default()
+        };
+        let search_options = SearchOptions {
+            iterations: 2_000,
+            rng_seed: Some(0),
+            ..Default::default()
+        };
+
+        let solution = gearset
+            .optimize_melds(&recipe(), craft_options, &search_options, &materia)
+            .unwrap();
+        assert!(solution.cost > 0);
+        let context = CraftContext::new(&solution.gearset.player(), &recipe(), craft_options);
+        let (state, _) = Simulator::simulate(&context, solution.actions);
+        assert!(state.quality >= context.quality_target);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::Recipe;
+
+/// An ingredient of a recipe that can contribute to starting quality
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ingredient {
+    /// Item level of the ingredient
+    pub level: u32,
+    /// Amount of the ingredient used in high quality
+    pub hq_amount: u32,
+    /// Total amount of the ingredient required by the recipe
+    pub amount: u32,
+}
+
+impl Ingredient {
+    pub fn new(level: u32, hq_amount: u32, amount: u32) -> Self {
+        Self {
+            level,
+            hq_amount,
+            amount,
+        }
+    }
+
+    /// Calculates the starting quality of a craft from its HQ ingredients.
+    /// Ingredients contribute to quality based on their item level, up to
+    /// `material_quality_factor` percent of the recipe's max quality (50 for
+    /// most recipes). Only ingredients that can be HQ should be included.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn starting_quality(
+        recipe: &Recipe,
+        material_quality_factor: u32,
+        ingredients: &[Ingredient],
+    ) -> u32 {
+        let total_levels: u64 = ingredients
+            .iter()
+            .map(|i| u64::from(i.level) * u64::from(i.amount))
+            .sum();
+        if total_levels == 0 {
+            return 0;
+        }
+        let hq_levels: u64 = ingredients
+            .iter()
+            .map(|i| u64::from(i.level) * u64::from(i.hq_amount.min(i.amount)))
+            .sum();
+
+        let max_quality = u64::from(recipe.quality) * u64::from(material_quality_factor) / 100;
+        (max_quality * hq_levels / total_levels) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ingredient;
+    use crate::test_fixtures::recipe;
+
+    #[test]
+    fn no_hq_ingredients() {
+        let ingredients = [Ingredient::new(560, 0, 2), Ingredient::new(570, 0, 1)];
+        assert_eq!(Ingredient::starting_quality(&recipe(), 50, &ingredients), 0);
+        assert_eq!(Ingredient::starting_quality(&recipe(), 50, &[]), 0);
+    }
+
+    #[test]
+    fn all_hq_ingredients() {
+        let ingredients = [Ingredient::new(560, 2, 2), Ingredient::new(570, 1, 1)];
+        assert_eq!(
+            Ingredient::starting_quality(&recipe(), 50, &ingredients),
+            3600
+        );
+    }
+
+    #[test]
+    fn weighs_by_item_level() {
+        // 560 / (560 * 2 + 570) of 3600 quality
+        let ingredients = [Ingredient::new(560, 1, 2), Ingredient::new(570, 0, 1)];
+        assert_eq!(
+            Ingredient::starting_quality(&recipe(), 50, &ingredients),
+            1192
+        );
+    }
+}
@@ -55,7 +55,7 @@ unsafe fn nth_bit_set_32_bmi2(value: u32, n: u32) -> u32 {
 }
 
 fn nth_bit_set_64_scalar(value: u64, mut n: u32) -> u32 {
-    let mut mask = 0x00000000FFFFFFFFu64;
+    let mut mask = 0x0000_0000_FFFF_FFFF_u64;
     let mut size = 32;
     let mut base = 0;
 
@@ -80,7 +80,7 @@ fn nth_bit_set_64_scalar(value: u64, mut n: u32) -> u32 {
 }
 
 fn nth_bit_set_32_scalar(value: u32, mut n: u32) -> u32 {
-    let mut mask = 0x0000FFFFu32;
+    let mut mask = 0x0000_FFFF_u32;
     let mut size = 16;
     let mut base = 0;
 
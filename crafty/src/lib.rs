@@ -2,21 +2,50 @@
 #![allow(clippy::must_use_candidate)]
 #![allow(clippy::return_self_not_must_use)]
 #![allow(clippy::enum_glob_use)]
+#![allow(clippy::used_underscore_items)]
 
 mod action;
 mod action_set;
+mod beam_search;
+mod best_first;
+mod breakpoint;
+mod condition;
+mod consumable;
 mod craft_context;
 mod craft_state;
+mod exact;
+mod gearset;
+mod ingredient;
 mod intrinsics;
 mod player;
 mod recipe;
 mod simulator;
+mod solver;
+#[cfg(test)]
+mod test_fixtures;
 mod tree;
+mod tree_export;
 
 pub use action::Action;
 use action_set::{ActionSet, BitFlagExt};
-pub use craft_context::{CraftContext, CraftOptions};
-pub use craft_state::{Buffs, CraftResult, CraftState};
+pub use beam_search::{BeamOptions, BeamSearch};
+pub use best_first::{BestFirstOptions, BestFirstSearch};
+pub use breakpoint::{Breakpoint, Breakpoints};
+pub use condition::{Condition, ConditionSet};
+pub use consumable::{Consumable, StatBonus};
+pub use craft_context::{CraftContext, CraftContextError, CraftOptions};
+pub use craft_state::{Buffs, CraftResult, CraftState, CraftStateSnapshot, Outcome};
+pub use exact::{ExactOptions, ExactSearch, ExactSolution};
+pub use gearset::{
+    GearItem, Gearset, Materia, MateriaOption, MateriaStat, MeldError, MeldSolution, Stats,
+    MAX_MELD_CANDIDATES,
+};
+pub use ingredient::Ingredient;
 pub use player::Player;
-pub use recipe::Recipe;
-pub use simulator::{SearchOptions, Simulator};
+pub use recipe::{Recipe, RecipeError, RecipeLevel, RecipeLevelTable};
+pub use simulator::{
+    AnytimeSearch, CancellationToken, Policy, ProgressCallback, RolloutCounts, SearchOptions,
+    SearchProgress, SearchStats, Simulator,
+};
+pub use solver::{BeamSolver, BestFirstSolver, ExactSolver, MctsSolver, Solution, Solver};
+pub use tree_export::{ExportedEdge, ExportedNode, TreeExport, TreeExportOptions};
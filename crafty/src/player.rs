@@ -1,5 +1,7 @@
+use crate::{Consumable, StatBonus};
 use std::fmt;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Player {
     pub job_level: u32,
     pub craftsmanship: u32,
@@ -16,6 +18,24 @@ impl Player {
             cp,
         }
     }
+
+    /// Returns a copy of the player with the bonuses of each consumable added
+    pub fn with_consumables(&self, consumables: &[Consumable]) -> Player {
+        let bonus = |stat: u32, get: fn(&Consumable) -> Option<StatBonus>| -> u32 {
+            consumables
+                .iter()
+                .filter_map(get)
+                .map(|bonus| bonus.calc(stat))
+                .sum()
+        };
+
+        Player::new(
+            self.job_level,
+            self.craftsmanship + bonus(self.craftsmanship, |c| c.craftsmanship),
+            self.control + bonus(self.control, |c| c.control),
+            self.cp + bonus(self.cp, |c| c.cp),
+        )
+    }
 }
 
 impl fmt::Display for Player {
@@ -1,8 +1,10 @@
-use std::fmt;
+use std::{collections::BTreeMap, error, fmt};
 
-// Must be separate from the `crafty` crate so it can be used in the build script
+// Must not depend on the rest of the `crafty` crate, since the `recipes` build
+// script includes this file directly
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Recipe {
     pub recipe_level: u32,
     pub job_level: u32,
@@ -28,3 +30,263 @@ impl fmt::Display for Recipe {
         )
     }
 }
+
+/// A row of the game's `RecipeLevelTable`. Everything here is shared by all
+/// recipes with the same recipe level.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecipeLevel {
+    pub recipe_level: u32,
+    pub job_level: u32,
+    pub stars: u32,
+    /// Base progress, quality, and durability, which each recipe scales by its
+    /// own factor
+    pub progress: u32,
+    pub quality: u32,
+    pub durability: i8,
+    pub progress_div: u32,
+    pub progress_mod: u32,
+    pub quality_div: u32,
+    pub quality_mod: u32,
+    pub conditions_flag: u32,
+}
+
+impl RecipeLevel {
+    fn recipe(&self, progress: u32, quality: u32, durability: i8, is_expert: bool) -> Recipe {
+        Recipe {
+            recipe_level: self.recipe_level,
+            job_level: self.job_level,
+            stars: self.stars,
+            progress,
+            quality,
+            durability,
+            progress_div: self.progress_div,
+            progress_mod: self.progress_mod,
+            quality_div: self.quality_div,
+            quality_mod: self.quality_mod,
+            is_expert,
+            conditions_flag: self.conditions_flag,
+        }
+    }
+
+    /// Creates a recipe at this level from the percentages its progress,
+    /// quality, and durability are scaled by, as in the game's `Recipe` sheet
+    pub fn recipe_from_factors(
+        &self,
+        progress_factor: u32,
+        quality_factor: u32,
+        durability_factor: u32,
+        is_expert: bool,
+    ) -> Recipe {
+        let scale = |base: u32, factor: u32| {
+            u32::try_from(u64::from(base) * u64::from(factor) / 100).unwrap_or(u32::MAX)
+        };
+        let durability = i64::from(self.durability) * i64::from(durability_factor) / 100;
+        self.recipe(
+            scale(self.progress, progress_factor),
+            scale(self.quality, quality_factor),
+            i8::try_from(durability).unwrap_or(i8::MAX),
+            is_expert,
+        )
+    }
+}
+
+/// Reasons a recipe doesn't match the recipe level table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipeError {
+    /// The table has no row for the recipe level
+    UnknownRecipeLevel(u32),
+    /// A value differs from the one in the table
+    Mismatch {
+        field: &'static str,
+        expected: u32,
+        found: u32,
+    },
+}
+
+impl fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownRecipeLevel(recipe_level) => {
+                write!(f, "recipe level {recipe_level} isn't in the table")
+            }
+            Self::Mismatch {
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "recipe {field} should be {expected} for this recipe level, got {found}"
+            ),
+        }
+    }
+}
+
+impl error::Error for RecipeError {}
+
+/// The recipe level table, keyed by recipe level. Fills in the values every
+/// recipe at a level shares, so they don't have to be passed for each recipe.
+#[derive(Debug, Clone, Default)]
+pub struct RecipeLevelTable {
+    levels: BTreeMap<u32, RecipeLevel>,
+}
+
+impl FromIterator<RecipeLevel> for RecipeLevelTable {
+    fn from_iter<T: IntoIterator<Item = RecipeLevel>>(levels: T) -> Self {
+        Self {
+            levels: levels
+                .into_iter()
+                .map(|level| (level.recipe_level, level))
+                .collect(),
+        }
+    }
+}
+
+impl RecipeLevelTable {
+    pub fn get(&self, recipe_level: u32) -> Option<&RecipeLevel> {
+        self.levels.get(&recipe_level)
+    }
+
+    /// Every row, sorted by recipe level
+    pub fn iter(&self) -> impl Iterator<Item = &RecipeLevel> {
+        self.levels.values()
+    }
+
+    /// Creates a recipe from the values that differ between recipes, filling
+    /// in the rest from the table.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `RecipeError` if the recipe level isn't in the table, or if
+    /// `stars` doesn't match its row.
+    pub fn recipe(
+        &self,
+        recipe_level: u32,
+        stars: u32,
+        progress: u32,
+        quality: u32,
+        durability: i8,
+        is_expert: bool,
+    ) -> Result<Recipe, RecipeError> {
+        let level = self
+            .get(recipe_level)
+            .ok_or(RecipeError::UnknownRecipeLevel(recipe_level))?;
+        if stars != level.stars {
+            return Err(RecipeError::Mismatch {
+                field: "stars",
+                expected: level.stars,
+                found: stars,
+            });
+        }
+        Ok(level.recipe(progress, quality, durability, is_expert))
+    }
+
+    /// Checks that the values a recipe shares with its recipe level match the
+    /// table.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `RecipeError` for the recipe level if it isn't in the table,
+    /// or for the first value that doesn't match.
+    pub fn validate(&self, recipe: &Recipe) -> Result<(), RecipeError> {
+        let level = self
+            .get(recipe.recipe_level)
+            .ok_or(RecipeError::UnknownRecipeLevel(recipe.recipe_level))?;
+        let fields = [
+            ("job level", level.job_level, recipe.job_level),
+            ("stars", level.stars, recipe.stars),
+            ("progress divider", level.progress_div, recipe.progress_div),
+            ("progress modifier", level.progress_mod, recipe.progress_mod),
+            ("quality divider", level.quality_div, recipe.quality_div),
+            ("quality modifier", level.quality_mod, recipe.quality_mod),
+            (
+                "conditions flag",
+                level.conditions_flag,
+                recipe.conditions_flag,
+            ),
+        ];
+        match fields
+            .into_iter()
+            .find(|&(_, expected, found)| expected != found)
+        {
+            Some((field, expected, found)) => Err(RecipeError::Mismatch {
+                field,
+                expected,
+                found,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    fn table() -> RecipeLevelTable {
+        [RecipeLevel {
+            recipe_level: 560,
+            job_level: 90,
+            stars: 0,
+            progress: 3500,
+            quality: 7200,
+            durability: 80,
+            progress_div: 130,
+            progress_mod: 90,
+            quality_div: 115,
+            quality_mod: 80,
+            conditions_flag: 15,
+        }]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn fills_level_values() {
+        let recipe = table().recipe(560, 0, 3500, 7200, 80, false).unwrap();
+        assert_eq!(recipe, test_fixtures::recipe());
+        assert_eq!(table().validate(&recipe), Ok(()));
+
+        let scaled = table()
+            .get(560)
+            .unwrap()
+            .recipe_from_factors(50, 100, 50, false);
+        assert_eq!(
+            (scaled.progress, scaled.quality, scaled.durability),
+            (1750, 7200, 40)
+        );
+    }
+
+    #[test]
+    fn rejects_values_not_in_table() {
+        assert_eq!(
+            table().recipe(561, 0, 3500, 7200, 80, false),
+            Err(RecipeError::UnknownRecipeLevel(561))
+        );
+        let stars_mismatch = RecipeError::Mismatch {
+            field: "stars",
+            expected: 0,
+            found: 2,
+        };
+        assert_eq!(
+            table().recipe(560, 2, 3500, 7200, 80, false),
+            Err(stars_mismatch.clone())
+        );
+        let recipe = Recipe {
+            stars: 2,
+            ..table().recipe(560, 0, 3500, 7200, 80, false).unwrap()
+        };
+        assert_eq!(table().validate(&recipe), Err(stars_mismatch));
+
+        let recipe = Recipe {
+            quality_mod: 70,
+            ..table().recipe(560, 0, 3500, 7200, 80, false).unwrap()
+        };
+        let err = table().validate(&recipe).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "recipe quality modifier should be 80 for this recipe level, got 70"
+        );
+    }
+}
@@ -1,11 +1,152 @@
-use crate::{tree::Arena, Action, BitFlagExt, CraftContext, CraftResult, CraftState};
+use crate::{
+    craft_state::StateKey, exact::QualityBound, tree::Arena, Action, ActionSet, BitFlagExt,
+    CraftContext, CraftResult, CraftState, ExportedEdge, ExportedNode, Outcome, TreeExport,
+    TreeExportOptions,
+};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-#[derive(Clone, Copy)]
+/// A snapshot of a search in progress
+#[derive(Debug, Clone)]
+pub struct SearchProgress {
+    /// Number of iterations run so far
+    pub iterations: u64,
+    /// The highest score reached by any simulation so far
+    pub best_score: f32,
+    /// The best rotation found so far, starting from the state the search
+    /// started from
+    pub best_actions: Vec<Action>,
+    /// Number of nodes in the search tree
+    pub nodes: usize,
+    /// Number of times a node without available moves was selected
+    pub dead_ends: u64,
+}
+
+/// Number of simulations that ended with each `CraftResult`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RolloutCounts {
+    pub finished: u64,
+    pub durability_failure: u64,
+    pub max_steps_failure: u64,
+    pub invalid_action_failure: u64,
+}
+
+impl RolloutCounts {
+    fn record(&mut self, result: &CraftResult) {
+        let count = match result {
+            CraftResult::Finished(_) => &mut self.finished,
+            CraftResult::DurabilityFailure => &mut self.durability_failure,
+            CraftResult::MaxStepsFailure => &mut self.max_steps_failure,
+            CraftResult::InvalidActionFailure => &mut self.invalid_action_failure,
+        };
+        *count += 1;
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.finished += other.finished;
+        self.durability_failure += other.durability_failure;
+        self.max_steps_failure += other.max_steps_failure;
+        self.invalid_action_failure += other.invalid_action_failure;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.finished
+            + self.durability_failure
+            + self.max_steps_failure
+            + self.invalid_action_failure
+    }
+}
+
+/// Diagnostics for tuning a search
+#[derive(Debug, Clone, Default)]
+pub struct SearchStats {
+    /// Number of iterations run
+    pub iterations: u64,
+    /// Number of nodes in the search tree
+    pub nodes: usize,
+    /// The most nodes below the starting node reached in one iteration,
+    /// including nodes stored from a simulation
+    pub max_depth: usize,
+    /// Number of times a node without available moves was selected
+    pub dead_ends: u64,
+    pub rollouts: RolloutCounts,
+    /// The highest score reached after each `progress_interval` iterations.
+    /// Searches with more than one thread count iterations per thread
+    pub best_score_history: Vec<f32>,
+    /// Approximate heap memory used by the search tree, in bytes
+    pub memory_bytes: usize,
+}
+
+impl SearchStats {
+    /// Combines stats from a search run after this one. Searches may reuse
+    /// part of the previous tree, so node counts and memory usage are the
+    /// peaks across searches.
+    fn add(&mut self, other: &Self) {
+        self.iterations += other.iterations;
+        self.nodes = self.nodes.max(other.nodes);
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.dead_ends += other.dead_ends;
+        self.rollouts.add(&other.rollouts);
+        self.best_score_history
+            .extend_from_slice(&other.best_score_history);
+        self.memory_bytes = self.memory_bytes.max(other.memory_bytes);
+    }
+}
+
+/// A function to call with the progress of a search. Clones share the same
+/// function.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(&SearchProgress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(&SearchProgress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// A flag that can be set from another thread to stop a search early. Clones
+/// share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct SearchOptions {
     /// Number of simulations to run
     pub iterations: u32,
-    /// Numerical seed to use for RNG. Randomly picked if None
+    /// Numerical seed to use for RNG. Seeded from system entropy when the
+    /// search starts if None, so set this on platforms without an entropy
+    /// source
     pub rng_seed: Option<u32>,
     /// A memory optimization option that specifies the minimum score a craft has
     /// to reach for action history to be stored. Only stores ~100% HQ states if None.
@@ -16,23 +157,95 @@ pub struct SearchOptions {
     pub max_score_weighting_constant: Option<f32>,
     /// Higher values prioritize exploring less promising nodes.
     pub exploration_constant: Option<f32>,
+    /// Number of threads to search with. Each thread searches its own tree
+    /// from the same starting state with a share of the iterations, and the
+    /// trees are merged afterwards. Results depend only on the seed and the
+    /// thread count.
+    pub threads: Option<u32>,
+    /// Stops the search once this much time has passed, even if there are
+    /// iterations left. Searches until the iterations run out if None. The
+    /// clock is only read when this is set, since `Instant` isn't available
+    /// in WebAssembly
+    pub time_limit: Option<Duration>,
+    /// Looks for the shortest rotation that finishes the craft at the quality
+    /// target, which is returned instead of the highest scoring one. The
+    /// search keeps going after the first such rotation, and only stops early
+    /// once no shorter rotation could reach the target, using the same
+    /// optimistic quality bound as `ExactSearch`. Ignored by stochastic
+    /// searches.
+    pub stop_at_quality_target: Option<bool>,
+    /// Called with the progress of the search every `progress_interval`
+    /// iterations, and once more when the search ends unless the last
+    /// iteration was just reported. Searches with more than one thread only
+    /// report progress after the threads are done. Skipped when serializing.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub progress_callback: Option<ProgressCallback>,
+    /// Number of iterations between progress reports
+    pub progress_interval: Option<u32>,
+    /// Stops the search once cancelled. Stepwise searches return the actions
+    /// picked so far. Skipped when serializing.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cancellation_token: Option<CancellationToken>,
 }
 
 impl Default for SearchOptions {
     fn default() -> Self {
         Self {
             iterations: 10_000,
-            rng_seed: Some(SmallRng::from_entropy().gen()),
+            rng_seed: None,
             score_storage_threshold: Some(1.0),
             max_score_weighting_constant: Some(0.1),
             exploration_constant: Some(1.5),
+            threads: Some(1),
+            time_limit: None,
+            stop_at_quality_target: Some(false),
+            progress_callback: None,
+            progress_interval: Some(1_000),
+            cancellation_token: None,
         }
     }
 }
 
+/// The result of a stochastic search: the best action for the current step,
+/// and what to follow up with depending on whether it succeeds and which
+/// condition gets rolled.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    /// The action to use in the current state
+    pub action: Action,
+    /// The average score of the simulations that started with `action`
+    pub expected_score: f32,
+    /// The best action for each random outcome of `action`. `None` if the
+    /// craft ends, or if the outcome was never explored.
+    pub next_actions: Vec<(Outcome, Option<Action>)>,
+}
+
+/// Picks an index from a list of outcomes, weighted by their probabilities
+fn sample_outcome(rng: &mut SmallRng, outcomes: &[Outcome]) -> usize {
+    let mut roll: f32 = rng.gen();
+    for (index, outcome) in outcomes.iter().enumerate() {
+        if roll < outcome.probability {
+            return index;
+        }
+        roll -= outcome.probability;
+    }
+    outcomes.len() - 1
+}
+
+/// The score of a rollout that ended with `result`
+fn result_score(result: &CraftResult) -> f32 {
+    match result {
+        CraftResult::Finished(s) => *s,
+        _ => 0.0,
+    }
+}
+
 #[derive(Debug)]
 pub struct Simulator<'a> {
     tree: Arena<CraftState<'a>>,
+    /// Moves available at the root before any were expanded, so that parallel
+    /// searches can start their own trees from the root
+    root_moves: ActionSet,
 
     // from SearchOptions
     iterations: u32,
@@ -40,22 +253,64 @@ pub struct Simulator<'a> {
     score_storage_threshold: f32,
     max_score_weighting_constant: f32,
     exploration_constant: f32,
+    deadline: Option<Instant>,
+    stop_at_quality_target: bool,
+    progress_callback: Option<ProgressCallback>,
+    progress_interval: u32,
+    cancellation_token: Option<CancellationToken>,
+
+    /// Number of iterations run so far, which may be fewer than `iterations`
+    /// if the search stopped early
+    iterations_run: u64,
+    /// The shortest rotation from the root that finished the craft at the
+    /// quality target, if `stop_at_quality_target` is set
+    target_rotation: Option<(Vec<Action>, CraftState<'a>)>,
+    /// The fewest steps any rotation from the root could finish at the
+    /// quality target in, if `stop_at_quality_target` is set
+    min_target_step: Option<u8>,
 
     /// Amount of "dead ends" encountered. This means a node was selected, but
     /// there weren't any available moves.
     dead_ends_selected: u64,
+    max_depth: usize,
+    rollouts: RolloutCounts,
+    best_score_history: Vec<f32>,
+
+    /// Whether random conditions are modeled with chance nodes. When enabled,
+    /// the tree alternates between decision nodes (a state with a known
+    /// condition) and chance nodes (an action whose outcome is unknown).
+    stochastic: bool,
+
+    threads: u32,
+
+    /// Index of the node for each gameplay state in the tree, so that states
+    /// reached with the same actions in a different order share a node. This
+    /// turns the tree into a DAG, so statistics are backpropagated along the
+    /// path taken during selection rather than through parent nodes. Only used
+    /// in deterministic searches.
+    transpositions: HashMap<StateKey, usize>,
+    /// Actions leading to nodes from parents other than the one that first
+    /// inserted them
+    transposition_actions: HashMap<(usize, usize), Action>,
 }
 
 impl<'a> Simulator<'a> {
     fn from_state(state: CraftState<'a>, options: SearchOptions) -> Self {
         let defaults = SearchOptions::default();
-        let rng_seed = options.rng_seed.or(defaults.rng_seed).unwrap();
 
-        Self {
+        let mut sim = Self {
+            transpositions: HashMap::from([(state.key(), 0)]),
+            transposition_actions: HashMap::new(),
+            root_moves: state.available_moves,
             tree: Arena::new(state),
             iterations: options.iterations,
             dead_ends_selected: 0,
-            rng: SmallRng::seed_from_u64(u64::from(rng_seed)),
+            stochastic: false,
+            rng: options
+                .rng_seed
+                .map_or_else(SmallRng::from_entropy, |rng_seed| {
+                    SmallRng::seed_from_u64(u64::from(rng_seed))
+                }),
             score_storage_threshold: options
                 .score_storage_threshold
                 .or(defaults.score_storage_threshold)
@@ -68,13 +323,162 @@ impl<'a> Simulator<'a> {
                 .exploration_constant
                 .or(defaults.exploration_constant)
                 .unwrap(),
+            threads: options.threads.or(defaults.threads).unwrap().max(1),
+            deadline: options
+                .time_limit
+                .map(|time_limit| Instant::now() + time_limit),
+            stop_at_quality_target: options
+                .stop_at_quality_target
+                .or(defaults.stop_at_quality_target)
+                .unwrap(),
+            progress_callback: options.progress_callback,
+            progress_interval: options
+                .progress_interval
+                .or(defaults.progress_interval)
+                .unwrap()
+                .max(1),
+            cancellation_token: options.cancellation_token,
+            iterations_run: 0,
+            target_rotation: None,
+            min_target_step: None,
+            max_depth: 0,
+            rollouts: RolloutCounts::default(),
+            best_score_history: vec![],
+        };
+        sim.update_min_target_step();
+        sim
+    }
+
+    /// Creates a single-threaded simulator with the same search parameters,
+    /// for searching a separate tree in another thread.
+    fn fork(&self, state: CraftState<'a>, iterations: u32, rng_seed: u64) -> Self {
+        Self {
+            transpositions: HashMap::from([(state.key(), 0)]),
+            transposition_actions: HashMap::new(),
+            root_moves: state.available_moves,
+            tree: Arena::new(state),
+            iterations,
+            rng: SmallRng::seed_from_u64(rng_seed),
+            score_storage_threshold: self.score_storage_threshold,
+            max_score_weighting_constant: self.max_score_weighting_constant,
+            exploration_constant: self.exploration_constant,
+            deadline: self.deadline,
+            stop_at_quality_target: self.stop_at_quality_target,
+            // forks are merged before progress is reported
+            progress_callback: None,
+            progress_interval: self.progress_interval,
+            cancellation_token: self.cancellation_token.clone(),
+            iterations_run: 0,
+            target_rotation: None,
+            min_target_step: self.min_target_step,
+            max_depth: 0,
+            rollouts: RolloutCounts::default(),
+            best_score_history: vec![],
+            dead_ends_selected: 0,
+            stochastic: self.stochastic,
+            threads: 1,
         }
     }
 
+    /// Finds the fewest steps a rotation from the root could reach the quality
+    /// target in, if the search should stop there
+    fn update_min_target_step(&mut self) {
+        let root_state = &self.tree.get(0).state;
+        self.min_target_step = if self.stop_at_quality_target {
+            QualityBound::new(root_state.context).min_target_step(root_state)
+        } else {
+            None
+        };
+    }
+
     fn from_context(context: &'a CraftContext, options: SearchOptions) -> Self {
         Self::from_state(CraftState::new(context), options)
     }
 
+    /// Makes a node the root of the tree, keeping everything below it so that
+    /// the next search starts with its statistics. Stats about the search so
+    /// far are reset.
+    fn reroot(&mut self, index: usize) {
+        // the shortest rotation to the quality target is still valid if it
+        // starts with the action to the new root
+        let action = self.edge_action(0, index);
+        self.target_rotation = self
+            .target_rotation
+            .take()
+            .filter(|(actions, _)| actions.first().copied() == action)
+            .map(|(actions, state)| (actions[1..].to_vec(), state));
+
+        let mapping = self.tree.reroot(index);
+        // the new root's moves were used up when it was expanded
+        let root_state = &self.tree.get(0).state;
+        self.root_moves = if self.stochastic {
+            root_state.clone_stochastic().available_moves
+        } else {
+            root_state.clone_strict().available_moves
+        };
+        self.transpositions = self
+            .transpositions
+            .drain()
+            .filter_map(|(key, index)| mapping[index].map(|index| (key, index)))
+            .collect();
+        self.transposition_actions = self
+            .transposition_actions
+            .drain()
+            .filter_map(|((parent, child), action)| {
+                Some(((mapping[parent]?, mapping[child]?), action))
+            })
+            .collect();
+
+        self.iterations_run = 0;
+        self.update_min_target_step();
+        self.max_depth = 0;
+        self.rollouts = RolloutCounts::default();
+        self.best_score_history.clear();
+    }
+
+    /// Inserts a state as a child of a node. In deterministic searches, an
+    /// equivalent state that's already in the tree is linked to instead.
+    fn insert(&mut self, parent_index: usize, state: CraftState<'a>) -> usize {
+        if self.stochastic {
+            return self.tree.insert(parent_index, state);
+        }
+
+        let key = state.key();
+        if let Some(&index) = self.transpositions.get(&key) {
+            if !self.tree.get(parent_index).children.contains(&index) {
+                self.tree.link(parent_index, index);
+                self.transposition_actions
+                    .insert((parent_index, index), state.action.unwrap());
+            }
+            return index;
+        }
+
+        let index = self.tree.insert(parent_index, state);
+        self.transpositions.insert(key, index);
+        index
+    }
+
+    /// The action that leads from a node to one of its children
+    fn edge_action(&self, parent_index: usize, child_index: usize) -> Option<Action> {
+        let child = self.tree.get(child_index);
+        if child.parent == Some(parent_index) {
+            child.state.action
+        } else {
+            self.transposition_actions
+                .get(&(parent_index, child_index))
+                .copied()
+        }
+    }
+
+    fn child_by_action(&self, parent_index: usize, action: Action) -> Option<usize> {
+        self.tree
+            .get(parent_index)
+            .children
+            .iter()
+            .copied()
+            .find(|&child_index| self.edge_action(parent_index, child_index) == Some(action))
+    }
+
     /// Executes a series of actions with most game-valid moves available. Will
     /// return early with `CraftResult::InvalidActionFailure` if an illegal move
     /// is chosen.
@@ -108,36 +512,30 @@ impl<'a> Simulator<'a> {
         (current_index, current_state.check_result())
     }
 
-    /// Executes a series of actions with strict move pruning enabled.
-    fn execute_actions_strict(
-        &mut self,
-        start_index: usize,
-        actions: Vec<Action>,
-    ) -> (usize, Option<CraftResult>) {
-        let mut current_index = start_index;
+    /// Executes a series of actions with strict move pruning enabled, starting
+    /// from the last node in `path` and adding each node reached to it. Stops
+    /// early if the craft ends or an action isn't available.
+    fn execute_actions_strict(&mut self, path: &mut Vec<usize>, actions: Vec<Action>) {
         for action in actions {
-            let current_state = &mut self.tree.get_mut(current_index).state;
+            let current_index = *path.last().unwrap();
 
-            if let Some(result) = current_state.check_result() {
-                return (current_index, Some(result));
+            // the action may have already been expanded from a transposition
+            if let Some(child_index) = self.child_by_action(current_index, action) {
+                path.push(child_index);
+                continue;
             }
 
-            // the next action must be available to use
-            if current_state.available_moves.contains(action) {
-                current_state.available_moves.remove(action);
-            } else {
-                return (current_index, Some(CraftResult::InvalidActionFailure));
+            let current_state = &mut self.tree.get_mut(current_index).state;
+            if current_state.check_result().is_some()
+                || !current_state.available_moves.contains(action)
+            {
+                return;
             }
+            current_state.available_moves.remove(action);
 
             let next_state = current_state.execute_strict(&action);
-            let next_index = self.tree.insert(current_index, next_state);
-
-            current_index = next_index;
+            path.push(self.insert(current_index, next_state));
         }
-
-        // check state after performing the last action
-        let current_state = &self.tree.get_mut(current_index).state;
-        (current_index, current_state.check_result())
     }
 
     /// Calculate the UCB1 score for a node
@@ -154,11 +552,12 @@ impl<'a> Simulator<'a> {
         exploitation + exploration
     }
 
-    /// Traverses the tree to find a good candidate node to expand.
-    fn select(&self, current_index: usize) -> usize {
-        let mut selected_index = current_index;
+    /// Traverses the tree to find a good candidate node to expand. Returns the
+    /// path taken from the starting node.
+    fn select(&self, start_index: usize) -> Vec<usize> {
+        let mut path = vec![start_index];
         loop {
-            let selected_node = self.tree.get(selected_index);
+            let selected_node = self.tree.get(*path.last().unwrap());
 
             let expandable = !selected_node.state.available_moves.is_empty();
             let likely_terminal = selected_node.children.is_empty();
@@ -167,7 +566,7 @@ impl<'a> Simulator<'a> {
             }
 
             // select the node with the highest score
-            selected_index = *selected_node
+            let selected_index = *selected_node
                 .children
                 .iter()
                 .max_by(|&a, &b| {
@@ -176,26 +575,32 @@ impl<'a> Simulator<'a> {
                     a_score.partial_cmp(&b_score).unwrap()
                 })
                 .unwrap();
+            path.push(selected_index);
         }
-        selected_index
+        path
     }
 
-    /// Expands the tree, then randomly selects from available moves until a
-    /// terminal state is encountered. To decrease memory usage, the tree should
-    /// only expand by one node per iteration unless we hit a good score, in
-    /// which case the the whole path should be stored.
-    fn expand_and_rollout(&mut self, initial_index: usize) -> (usize, CraftResult) {
+    /// Expands the tree from the last node in `path`, then randomly selects from
+    /// available moves until a terminal state is encountered. To decrease memory
+    /// usage, the tree should only expand by one node per iteration unless we
+    /// hit a good score, in which case the the whole path should be stored.
+    /// Nodes added to the tree are appended to `path`.
+    fn expand_and_rollout(&mut self, path: &mut Vec<usize>) -> CraftResult {
         // expand once
+        let initial_index = *path.last().unwrap();
         let initial_state = &mut self.tree.get_mut(initial_index).state;
         if let Some(result) = initial_state.check_result() {
-            return (initial_index, result);
+            return result;
         }
         let random_action = initial_state.available_moves.pick(&mut self.rng);
         let expanded_state = initial_state.execute_strict(&random_action);
-        let expanded_index = self.tree.insert(initial_index, expanded_state);
+
+        // a transposition may have had some of its moves expanded already, so
+        // play out from the new state instead
+        let mut current_state = expanded_state.clone();
+        path.push(self.insert(initial_index, expanded_state));
 
         // playout to a terminal state
-        let mut current_state = self.tree.get(expanded_index).state.clone();
         let mut action_history: Vec<Action> = vec![];
         let result = loop {
             if let Some(result) = current_state.check_result() {
@@ -206,64 +611,458 @@ impl<'a> Simulator<'a> {
             current_state = current_state.execute_strict(&random_action);
         };
 
-        // store the result if a max score was reached
-        match result {
-            CraftResult::Finished(score)
-                if score >= self.score_storage_threshold
-                    && score >= self.tree.nodes[0].state.max_score =>
+        // store the result if a max score was reached, or if it's the shortest
+        // rotation to the quality target so far
+        if let CraftResult::Finished(score) = result {
+            let is_target_rotation = self.stop_at_quality_target
+                && current_state.quality >= current_state.context.quality_target;
+            if is_target_rotation {
+                let actions = path
+                    .windows(2)
+                    .filter_map(|pair| self.edge_action(pair[0], pair[1]))
+                    .chain(action_history.iter().copied())
+                    .collect();
+                current_state.max_score = score;
+                self.offer_target_rotation(actions, current_state);
+            }
+            if is_target_rotation
+                || (score >= self.score_storage_threshold
+                    && score >= self.tree.nodes[0].state.max_score)
             {
-                let (terminal_index, _) =
-                    self.execute_actions_strict(expanded_index, action_history);
-                (terminal_index, result)
+                self.execute_actions_strict(path, action_history);
             }
-            _ => (expanded_index, result),
         }
+        result
     }
 
-    /// From a starting node, follow parent nodes back to the root node, updating
-    /// statistics for each node along the way.
-    fn backpropagate(&mut self, start_index: usize, target_index: usize, score: f32) {
-        let mut current_index = start_index;
+    /// Keeps a rotation that finished the craft at the quality target if it's
+    /// shorter than the one found so far, or as short with a higher score
+    fn offer_target_rotation(&mut self, actions: Vec<Action>, state: CraftState<'a>) {
+        let is_better = self.target_rotation.as_ref().is_none_or(|(_, best)| {
+            state.step < best.step || (state.step == best.step && state.max_score > best.max_score)
+        });
+        if is_better {
+            self.target_rotation = Some((actions, state));
+        }
+    }
+
+    /// Whether the shortest rotation to the quality target has been found,
+    /// since no rotation could reach it in fewer steps
+    fn found_shortest_target_rotation(&self) -> bool {
+        match (&self.target_rotation, self.min_target_step) {
+            (Some((_, state)), Some(min_target_step)) => state.step <= min_target_step,
+            _ => false,
+        }
+    }
+
+    /// Traverses a tree of alternating decision and chance nodes to find a
+    /// candidate node to expand. Decision nodes pick the child with the highest
+    /// UCB1 score, while chance nodes sample an outcome by its probability.
+    /// Returns the path taken from the starting node.
+    fn select_stochastic(&mut self, start_index: usize) -> Vec<usize> {
+        let mut path = vec![start_index];
+        let mut is_chance_node = false;
         loop {
-            // Mutate current node stats
-            let current_node = self.tree.get_mut(current_index);
-            current_node.state.visits += 1.0;
-            current_node.state.score_sum += score;
-            current_node.state.max_score = current_node.state.max_score.max(score);
+            let selected_index = *path.last().unwrap();
+            let selected_node = self.tree.get(selected_index);
 
-            if current_index == target_index {
+            let likely_terminal = selected_node.children.is_empty();
+            if likely_terminal {
                 break;
             }
 
-            current_index = current_node.parent.unwrap();
+            if is_chance_node {
+                // children of a chance node are ordered the same as the outcomes
+                let parent_state = &self.tree.get(path[path.len() - 2]).state;
+                let outcomes = parent_state.outcomes(&selected_node.state.action.unwrap());
+                let outcome_index = sample_outcome(&mut self.rng, &outcomes);
+                path.push(self.tree.get(selected_index).children[outcome_index]);
+            } else {
+                let expandable = !selected_node.state.available_moves.is_empty();
+                if expandable {
+                    break;
+                }
+
+                let next_index = *selected_node
+                    .children
+                    .iter()
+                    .max_by(|&a, &b| {
+                        let a_score = self.eval(&self.tree.get(*a).state, &selected_node.state);
+                        let b_score = self.eval(&self.tree.get(*b).state, &selected_node.state);
+                        a_score.partial_cmp(&b_score).unwrap()
+                    })
+                    .unwrap();
+                path.push(next_index);
+            }
+
+            is_chance_node = !is_chance_node;
+        }
+        path
+    }
+
+    /// Expands a decision node with a chance node, along with every possible
+    /// outcome of that chance node. One of the outcomes is then sampled and
+    /// played out to a terminal state, rolling random outcomes along the way.
+    /// If every outcome ends the craft, the chance node is scored by the
+    /// probability-weighted score of its outcomes instead. Nodes added to the
+    /// tree are appended to `path`. Returns the score to backpropagate along
+    /// with the result of the playout.
+    fn expand_and_rollout_stochastic(&mut self, path: &mut Vec<usize>) -> (f32, CraftResult) {
+        let initial_index = *path.last().unwrap();
+        let initial_state = &mut self.tree.get_mut(initial_index).state;
+        if let Some(result) = initial_state.check_result() {
+            return (result_score(&result), result);
+        }
+        let random_action = initial_state.available_moves.pick(&mut self.rng);
+        let outcomes = initial_state.outcomes(&random_action);
+        let chance_state = initial_state.execute_strict(&random_action);
+        let outcome_states: Vec<CraftState<'a>> = outcomes
+            .iter()
+            .map(|outcome| initial_state.execute_stochastic(&random_action, outcome))
+            .collect();
+
+        let mut outcome_results: Vec<Option<CraftResult>> = outcome_states
+            .iter()
+            .map(CraftState::check_result)
+            .collect();
+
+        let chance_index = self.tree.insert(initial_index, chance_state);
+        path.push(chance_index);
+        let outcome_indexes: Vec<usize> = outcome_states
+            .into_iter()
+            .map(|state| self.tree.insert(chance_index, state))
+            .collect();
+        let sampled = sample_outcome(&mut self.rng, &outcomes);
+
+        // the craft is over whichever outcome is rolled, e.g. a finishing
+        // action that can fail on the last step, so there's nothing to play out
+        if outcome_results.iter().all(Option::is_some) {
+            let score = outcomes
+                .iter()
+                .zip(&outcome_results)
+                .map(|(outcome, result)| {
+                    outcome.probability * result.as_ref().map_or(0.0, result_score)
+                })
+                .sum();
+            return (score, outcome_results.swap_remove(sampled).unwrap());
+        }
+
+        let expanded_index = outcome_indexes[sampled];
+        path.push(expanded_index);
+
+        // playout to a terminal state
+        let mut current_state = self.tree.get(expanded_index).state.clone();
+        let result = loop {
+            if let Some(result) = current_state.check_result() {
+                break result;
+            }
+            let random_action = current_state.available_moves.sample(&mut self.rng);
+            let outcomes = current_state.outcomes(&random_action);
+            let outcome = outcomes[sample_outcome(&mut self.rng, &outcomes)];
+            current_state = current_state.execute_stochastic(&random_action, &outcome);
+        };
+
+        (result_score(&result), result)
+    }
+
+    /// Updates statistics for each node along a path taken during an iteration.
+    fn backpropagate(&mut self, path: &[usize], score: f32) {
+        for &index in path {
+            let state = &mut self.tree.get_mut(index).state;
+            state.visits += 1.0;
+            state.score_sum += score;
+            state.max_score = state.max_score.max(score);
+        }
+    }
+
+    /// Adds the statistics of a node in another tree onto a node in this tree,
+    /// then recursively merges children that were reached with the same action.
+    /// Children of chance nodes are always created in the order of their
+    /// outcomes, so they're merged by position instead. Nodes that are shared
+    /// by several parents are only merged once.
+    fn merge(
+        &mut self,
+        index: usize,
+        other: &Self,
+        other_index: usize,
+        is_chance_node: bool,
+        merged: &mut HashMap<usize, usize>,
+    ) {
+        merged.insert(other_index, index);
+
+        let other_node = other.tree.get(other_index);
+        let node = self.tree.get_mut(index);
+        node.state.visits += other_node.state.visits;
+        node.state.score_sum += other_node.state.score_sum;
+        node.state.max_score = node.state.max_score.max(other_node.state.max_score);
+        // moves that were expanded in either tree are no longer available
+        node.state.available_moves &= other_node.state.available_moves;
+
+        for (position, &other_child_index) in other_node.children.iter().enumerate() {
+            let action = other.edge_action(other_index, other_child_index);
+            let existing_index = if is_chance_node {
+                self.tree.get(index).children.get(position).copied()
+            } else {
+                action.and_then(|action| self.child_by_action(index, action))
+            };
+
+            let child_index = existing_index.unwrap_or_else(|| {
+                let mut state = other.tree.get(other_child_index).state.clone();
+                state.action = action;
+                state.visits = 0.0;
+                state.score_sum = 0.0;
+                state.max_score = 0.0;
+                self.insert(index, state)
+            });
+            if !merged.contains_key(&other_child_index) {
+                let child_is_chance_node = self.stochastic && !is_chance_node;
+                self.merge(
+                    child_index,
+                    other,
+                    other_child_index,
+                    child_is_chance_node,
+                    merged,
+                );
+            }
+        }
+    }
+
+    /// Root parallelization: each thread searches its own tree starting from
+    /// the root, and the trees are merged into this one in thread order.
+    fn search_parallel(&mut self, iterations: u32) -> &mut Self {
+        // the root's moves have been expanded in this tree, but each thread's
+        // tree starts out empty
+        let mut start_state = self.tree.get(0).state.clone();
+        start_state.available_moves = self.root_moves;
+        start_state.visits = 0.0;
+        start_state.score_sum = 0.0;
+        start_state.max_score = 0.0;
+
+        let threads = self.threads;
+        let forks: Vec<Self> = (0..threads)
+            .map(|thread_index| {
+                let iterations =
+                    iterations / threads + u32::from(thread_index < iterations % threads);
+                let rng_seed = self.rng.gen();
+                self.fork(start_state.clone(), iterations, rng_seed)
+            })
+            .collect();
+
+        let forks: Vec<Self> = thread::scope(|scope| {
+            let handles: Vec<_> = forks
+                .into_iter()
+                .map(|mut fork| {
+                    scope.spawn(move || {
+                        fork.run(0, fork.iterations);
+                        fork
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        // each thread records its own score history, so take the best score
+        // across threads at each point
+        let mut best_score_history: Vec<f32> = vec![];
+        for fork in forks {
+            self.dead_ends_selected += fork.dead_ends_selected;
+            self.iterations_run += fork.iterations_run;
+            if let Some((actions, state)) = fork.target_rotation.clone() {
+                self.offer_target_rotation(actions, state);
+            }
+            self.max_depth = self.max_depth.max(fork.max_depth);
+            self.rollouts.add(&fork.rollouts);
+            for (index, &score) in fork.best_score_history.iter().enumerate() {
+                match best_score_history.get_mut(index) {
+                    Some(best_score) => *best_score = best_score.max(score),
+                    None => best_score_history.push(score),
+                }
+            }
+            self.merge(0, &fork, 0, false, &mut HashMap::new());
         }
+        self.best_score_history.extend(best_score_history);
+        self.report_progress();
+        self
     }
 
     /// The starting point for one round of MCTS.
     fn search(&mut self, start_index: usize) -> &mut Self {
-        for _ in 0..self.iterations {
-            let selected_index = self.select(start_index);
-            let (end_index, result) = self.expand_and_rollout(selected_index);
+        self.run(start_index, self.iterations)
+    }
 
-            if selected_index == end_index {
-                self.dead_ends_selected += 1;
+    fn is_cancelled(&self) -> bool {
+        self.cancellation_token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Whether the search was cancelled, the time limit has passed, or the
+    /// shortest rotation to the quality target was found and the search should
+    /// stop there. Never stops before the first iteration, so that there's
+    /// always a move to pick afterwards.
+    fn should_stop(&self) -> bool {
+        self.iterations_run > 0
+            && (self.found_shortest_target_rotation()
+                || self.is_cancelled()
+                || self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline))
+    }
+
+    fn stats(&self) -> SearchStats {
+        SearchStats {
+            iterations: self.iterations_run,
+            nodes: self.tree.nodes.len(),
+            max_depth: self.max_depth,
+            dead_ends: self.dead_ends_selected,
+            rollouts: self.rollouts,
+            best_score_history: self.best_score_history.clone(),
+            memory_bytes: self.tree.memory_usage(),
+        }
+    }
+
+    /// Copies the most visited parts of the tree, walking breadth-first from
+    /// the root so that each node is exported at its shallowest depth.
+    #[allow(clippy::cast_precision_loss)]
+    fn export_tree(&self, options: &TreeExportOptions) -> TreeExport {
+        let export_node = |index: usize, depth: usize| {
+            let state = &self.tree.get(index).state;
+            ExportedNode {
+                id: index,
+                depth,
+                visits: state.visits,
+                average_score: if state.visits > 0.0 {
+                    state.score_sum / state.visits
+                } else {
+                    0.0
+                },
+                max_score: state.max_score,
+                progress: state.progress,
+                quality: state.quality,
+            }
+        };
+
+        let mut export = TreeExport::default();
+        export.nodes.push(export_node(0, 0));
+        let mut exported = HashSet::from([0]);
+        let mut queue = VecDeque::from([(0, 0)]);
+        while let Some((index, depth)) = queue.pop_front() {
+            if options
+                .max_depth
+                .is_some_and(|max_depth| depth >= max_depth)
+            {
+                continue;
+            }
+
+            let mut children: Vec<usize> = self
+                .tree
+                .get(index)
+                .children
+                .iter()
+                .copied()
+                .filter(|&child| self.tree.get(child).state.visits >= options.min_visits as f32)
+                .collect();
+            children.sort_by(|&a, &b| {
+                let a_visits = self.tree.get(a).state.visits;
+                let b_visits = self.tree.get(b).state.visits;
+                b_visits.total_cmp(&a_visits)
+            });
+            children.truncate(options.max_children.unwrap_or(usize::MAX));
+
+            for child in children {
+                export.edges.push(ExportedEdge {
+                    parent: index,
+                    child,
+                    action: self.edge_action(index, child),
+                });
+                if exported.insert(child) {
+                    export.nodes.push(export_node(child, depth + 1));
+                    queue.push_back((child, depth + 1));
+                }
             }
+        }
+        export
+    }
+
+    fn report_progress(&self) {
+        if let Some(progress_callback) = &self.progress_callback {
+            let (best_actions, _) = self.solution();
+            (progress_callback.0)(&SearchProgress {
+                iterations: self.iterations_run,
+                best_score: self.tree.get(0).state.max_score,
+                best_actions,
+                nodes: self.tree.nodes.len(),
+                dead_ends: self.dead_ends_selected,
+            });
+        }
+    }
+
+    /// Runs up to `iterations` iterations of MCTS, or fewer if the search
+    /// should stop early. Searches from the root are split across threads.
+    fn run(&mut self, start_index: usize, iterations: u32) -> &mut Self {
+        if self.threads > 1 && start_index == 0 {
+            return self.search_parallel(iterations);
+        }
 
-            let score = match result {
-                CraftResult::Finished(s) => s,
-                _ => 0.0,
+        // whether the last iteration was already reported
+        let mut reported = false;
+        for _ in 0..iterations {
+            let mut path = if self.stochastic {
+                self.select_stochastic(start_index)
+            } else {
+                self.select(start_index)
             };
-            self.backpropagate(end_index, start_index, score);
+            let selected_len = path.len();
+            let (score, result) = if self.stochastic {
+                self.expand_and_rollout_stochastic(&mut path)
+            } else {
+                let result = self.expand_and_rollout(&mut path);
+                (result_score(&result), result)
+            };
+
+            if path.len() == selected_len {
+                self.dead_ends_selected += 1;
+            }
+            self.max_depth = self.max_depth.max(path.len() - 1);
+            self.rollouts.record(&result);
+
+            self.backpropagate(&path, score);
+            self.iterations_run += 1;
+
+            reported = self
+                .iterations_run
+                .is_multiple_of(u64::from(self.progress_interval));
+            if reported {
+                self.best_score_history
+                    .push(self.tree.get(start_index).state.max_score);
+                self.report_progress();
+            }
+            if self.should_stop() {
+                break;
+            }
+        }
+        if !reported {
+            self.report_progress();
         }
         self
     }
 
     /// Traverses the current tree, following actions that result in the highest
     /// score to find the best solution. This is a convenient way to extract a
-    /// solution after running `search`.
+    /// solution after running `search`. If the search is looking for the
+    /// shortest rotation to the quality target, that's returned instead once
+    /// one is found.
     fn solution(&self) -> (Vec<Action>, CraftState<'a>) {
+        if let Some(target_rotation) = &self.target_rotation {
+            return target_rotation.clone();
+        }
+
         let mut actions = vec![];
-        let mut node = self.tree.get(0);
+        let mut index = 0;
+        let mut node = self.tree.get(index);
         while !node.children.is_empty() {
             let next_index: usize = *node
                 .children
@@ -274,13 +1073,56 @@ impl<'a> Simulator<'a> {
                     a_score.partial_cmp(&b_score).unwrap()
                 })
                 .unwrap();
-            node = self.tree.get(next_index);
-            if node.state.action.is_some() {
-                actions.push(node.state.action.unwrap());
+            if let Some(action) = self.edge_action(index, next_index) {
+                actions.push(action);
             }
+            index = next_index;
+            node = self.tree.get(index);
         }
 
-        (actions, node.state.clone())
+        let mut state = node.state.clone();
+        state.action = actions.last().copied().or(state.action);
+        (actions, state)
+    }
+
+    /// Extracts a `Policy` from a stochastic search tree. Actions are compared
+    /// by their average score, since max scores are only reachable with lucky
+    /// condition rolls.
+    fn policy(&self) -> Option<Policy> {
+        let average_score = |index: &usize| {
+            let state = &self.tree.get(*index).state;
+            state.score_sum / state.visits
+        };
+        let best_child = |children: &[usize]| {
+            children
+                .iter()
+                .filter(|&&index| self.tree.get(index).state.visits > 0.0)
+                .max_by(|a, b| average_score(a).partial_cmp(&average_score(b)).unwrap())
+                .copied()
+        };
+
+        let root = self.tree.get(0);
+        let chance_index = best_child(&root.children)?;
+        let chance_node = self.tree.get(chance_index);
+
+        let action = chance_node.state.action.unwrap();
+        let next_actions = root
+            .state
+            .outcomes(&action)
+            .into_iter()
+            .zip(chance_node.children.iter())
+            .map(|(outcome, &outcome_index)| {
+                let next_action = best_child(&self.tree.get(outcome_index).children)
+                    .and_then(|index| self.tree.get(index).state.action);
+                (outcome, next_action)
+            })
+            .collect();
+
+        Some(Policy {
+            action,
+            expected_score: average_score(&chance_index),
+            next_actions,
+        })
     }
 
     /// A standalone method to obtain a `CraftState` from a series of actions.
@@ -288,19 +1130,44 @@ impl<'a> Simulator<'a> {
         context: &'a CraftContext,
         actions: Vec<Action>,
     ) -> (CraftState<'a>, Option<CraftResult>) {
-        let mut sim = Self::from_context(context, SearchOptions::default());
+        // nothing is random here, so don't bother seeding from entropy
+        let search_options = SearchOptions {
+            rng_seed: Some(0),
+            ..Default::default()
+        };
+        let mut sim = Self::from_context(context, search_options);
         let (index, result) = sim.execute_actions(0, actions);
         (sim.tree.get(index).state.clone(), result)
     }
 
-    /// Searches for good actions step by step. Creates a fresh tree and runs a
-    /// new search from scratch for each action picked.
+    /// Searches for good actions step by step. After each action is picked, the
+    /// tree below it is kept for the next step, and only enough iterations are
+    /// run to bring its visits up to the iteration count.
     pub fn search_stepwise(
         context: &'a CraftContext,
         action_history: Vec<Action>,
         search_options: SearchOptions,
         action_callback: Option<&dyn Fn(Action)>,
     ) -> (Vec<Action>, CraftState<'a>) {
+        let (actions, state, _) = Self::search_stepwise_with_stats(
+            context,
+            action_history,
+            search_options,
+            action_callback,
+        );
+        (actions, state)
+    }
+
+    /// Same as `search_stepwise`, but also returns stats combined across every
+    /// step.
+    // the chosen action always comes from a child of the root
+    #[allow(clippy::missing_panics_doc)]
+    pub fn search_stepwise_with_stats(
+        context: &'a CraftContext,
+        action_history: Vec<Action>,
+        search_options: SearchOptions,
+        action_callback: Option<&dyn Fn(Action)>,
+    ) -> (Vec<Action>, CraftState<'a>, SearchStats) {
         // only store perfect scores to reduce memory usage
         let search_options = SearchOptions {
             score_storage_threshold: None,
@@ -309,71 +1176,205 @@ impl<'a> Simulator<'a> {
 
         let (start_state, result) = Self::simulate(context, action_history.clone());
         if result.is_some() {
-            return (action_history, start_state);
+            return (action_history, start_state, SearchStats::default());
         }
 
+        // the time limit applies to the whole search rather than each step
+        let deadline = search_options
+            .time_limit
+            .map(|time_limit| Instant::now() + time_limit);
+
         let mut state = start_state.clone_strict();
         let mut actions = action_history;
+        let mut search_stats = SearchStats::default();
+        let mut sim = Self::from_state(state.clone(), search_options.clone());
+        sim.deadline = deadline;
         while state.check_result().is_none() {
-            let mut sim = Self::from_state(state.clone(), search_options);
-            let (solution_actions, solution_state) = sim.search(0).solution();
+            if sim.is_cancelled() {
+                break;
+            }
+
+            // visits from earlier steps count towards this step's iterations
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let visits = sim.tree.get(0).state.visits as u32;
+            let iterations = sim.iterations.saturating_sub(visits).max(1);
+            let (solution_actions, solution_state) = sim.run(0, iterations).solution();
+            search_stats.add(&sim.stats());
 
-            if solution_state.max_score >= 1.0 {
-                return ([actions, solution_actions].concat(), solution_state);
+            if solution_state.max_score >= 1.0 || sim.found_shortest_target_rotation() {
+                return (
+                    [actions, solution_actions].concat(),
+                    solution_state,
+                    search_stats,
+                );
             }
 
             let chosen_action = solution_actions[0];
             state = state.execute_strict(&chosen_action);
             actions.push(chosen_action);
+            let chosen_index = sim.child_by_action(0, chosen_action).unwrap();
+            sim.reroot(chosen_index);
 
             if let Some(action_callback) = action_callback {
                 action_callback(chosen_action);
             }
         }
 
-        (actions, state)
+        (actions, state, search_stats)
+    }
+
+    /// Searches for the best action to use in `state` when conditions are
+    /// random. Each action leads to a chance node over its possible outcomes
+    /// (success or failure, and the condition rolled next), so rather than a
+    /// fixed rotation, a `Policy` for the current and next step is returned.
+    /// Returns `None` if the craft is over.
+    pub fn search_policy(state: &CraftState<'a>, search_options: SearchOptions) -> Option<Policy> {
+        let (policy, _) = Self::search_policy_with_stats(state, search_options);
+        policy
+    }
+
+    /// Same as `search_policy`, but also returns stats about the search.
+    pub fn search_policy_with_stats(
+        state: &CraftState<'a>,
+        search_options: SearchOptions,
+    ) -> (Option<Policy>, SearchStats) {
+        let mut sim = Self::from_state(state.clone_stochastic(), search_options);
+        sim.stochastic = true;
+        sim.search(0);
+        (sim.policy(), sim.stats())
     }
 
     /// Constructs a single large tree, storing good candidate paths in memory
     /// based on the `score_storage_threshold` option. When the iteration limit
     /// is reached, the action path that results in the highest score is returned.
+    /// The search starts from the state after `action_history`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the result of `action_history` if it already ends the craft,
+    /// either because an action couldn't be used or because the craft was
+    /// finished or failed.
     pub fn search_oneshot(
         context: &'a CraftContext,
         action_history: Vec<Action>,
         search_options: SearchOptions,
-    ) -> (Vec<Action>, CraftState<'a>) {
-        let mut sim = Self::from_context(context, search_options);
+    ) -> Result<(Vec<Action>, CraftState<'a>), CraftResult> {
+        let (actions, state, _) =
+            Self::search_oneshot_with_stats(context, action_history, search_options)?;
+        Ok((actions, state))
+    }
+
+    /// Same as `search_oneshot`, but also returns stats about the search.
+    ///
+    /// # Errors
+    ///
+    /// Returns the result of `action_history` if it already ends the craft.
+    pub fn search_oneshot_with_stats(
+        context: &'a CraftContext,
+        action_history: Vec<Action>,
+        search_options: SearchOptions,
+    ) -> Result<(Vec<Action>, CraftState<'a>, SearchStats), CraftResult> {
+        let (start_state, result) = Self::simulate(context, action_history.clone());
+        if let Some(result) = result {
+            return Err(result);
+        }
+
+        // the search assumes every action succeeds, so leave out ones that can fail
+        let mut sim = Self::from_state(start_state.clone_strict(), search_options);
         let (actions, result_state) = sim.search(0).solution();
-        ([action_history, actions].concat(), result_state)
+        Ok((
+            [action_history, actions].concat(),
+            result_state,
+            sim.stats(),
+        ))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{Action, CraftContext, CraftOptions, Player, Recipe, SearchOptions, Simulator};
-    use Action::*;
+/// A oneshot search that runs in chunks, so the best rotation found so far can
+/// be checked between them. The search ends once its iterations run out, the
+/// time limit passes, or the shortest rotation to the quality target is found
+/// if the search was set to stop there.
+#[derive(Debug)]
+pub struct AnytimeSearch<'a> {
+    sim: Simulator<'a>,
+    action_history: Vec<Action>,
+}
 
-    fn setup_1() -> (CraftContext, SearchOptions) {
-        let recipe = Recipe {
-            recipe_level: 560,
-            job_level: 90,
-            stars: 0,
-            progress: 3500,
-            quality: 7200,
-            durability: 80,
-            progress_div: 130,
-            progress_mod: 90,
-            quality_div: 115,
-            quality_mod: 80,
-            is_expert: false,
-            conditions_flag: 15,
-        };
-        let player = Player::new(90, 3304, 3374, 575);
-        let craft_options = CraftOptions {
-            max_steps: 25,
-            ..Default::default()
-        };
-        let context = CraftContext::new(&player, &recipe, craft_options);
+impl<'a> AnytimeSearch<'a> {
+    /// Prepares a search from the state after `action_history`. The time limit
+    /// starts counting from here.
+    ///
+    /// # Errors
+    ///
+    /// Returns the result of `action_history` if it already ends the craft.
+    pub fn new(
+        context: &'a CraftContext,
+        action_history: Vec<Action>,
+        search_options: SearchOptions,
+    ) -> Result<Self, CraftResult> {
+        let (start_state, result) = Simulator::simulate(context, action_history.clone());
+        if let Some(result) = result {
+            return Err(result);
+        }
+
+        Ok(Self {
+            sim: Simulator::from_state(start_state.clone_strict(), search_options),
+            action_history,
+        })
+    }
+
+    /// Runs up to `iterations` more iterations, without going over the total
+    /// set in `SearchOptions`. Returns whether the search can keep going.
+    pub fn run(&mut self, iterations: u32) -> bool {
+        if !self.is_done() {
+            let remaining = u64::from(self.sim.iterations) - self.sim.iterations_run;
+            let iterations = u32::try_from(remaining).map_or(iterations, |r| r.min(iterations));
+            self.sim.run(0, iterations);
+        }
+        !self.is_done()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.sim.iterations_run >= u64::from(self.sim.iterations) || self.sim.should_stop()
+    }
+
+    pub fn iterations_run(&self) -> u64 {
+        self.sim.iterations_run
+    }
+
+    pub fn stats(&self) -> SearchStats {
+        self.sim.stats()
+    }
+
+    /// Copies the most visited parts of the search tree for inspection, for
+    /// example with `TreeExport::to_dot`
+    pub fn export_tree(&self, options: &TreeExportOptions) -> TreeExport {
+        self.sim.export_tree(options)
+    }
+
+    /// The best rotation found so far, including the action history
+    pub fn best(&self) -> (Vec<Action>, CraftState<'a>) {
+        let (actions, state) = self.sim.solution();
+        ([self.action_history.clone(), actions].concat(), state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures;
+    use crate::{
+        Action, ActionSet, AnytimeSearch, CancellationToken, Condition, CraftContext, CraftOptions,
+        CraftResult, CraftState, Outcome, Player, ProgressCallback, Recipe, SearchOptions,
+        SearchProgress, Simulator, TreeExportOptions,
+    };
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use Action::*;
+
+    fn setup_1() -> (CraftContext, SearchOptions) {
+        let context = test_fixtures::context();
         let options = SearchOptions {
             rng_seed: Some(0),
             ..Default::default()
@@ -409,6 +1410,35 @@ mod tests {
         (context, options)
     }
 
+    fn setup_3() -> (CraftContext, SearchOptions) {
+        let recipe = Recipe {
+            recipe_level: 690,
+            job_level: 100,
+            stars: 0,
+            progress: 6600,
+            quality: 12000,
+            durability: 80,
+            progress_div: 170,
+            progress_mod: 90,
+            quality_div: 150,
+            quality_mod: 75,
+            is_expert: false,
+            conditions_flag: 15,
+        };
+        let player = Player::new(100, 4900, 4800, 620);
+        let craft_options = CraftOptions {
+            max_steps: 25,
+            is_specialist: true,
+            ..Default::default()
+        };
+        let context = CraftContext::new(&player, &recipe, craft_options);
+        let options = SearchOptions {
+            rng_seed: Some(0),
+            ..Default::default()
+        };
+        (context, options)
+    }
+
     fn assert_craft(
         context: &CraftContext,
         actions: Vec<Action>,
@@ -496,6 +1526,359 @@ mod tests {
         assert!(end_state.available_moves.contains(TrainedFinesse));
     }
 
+    fn execute_with_condition(
+        context: &CraftContext,
+        condition: Condition,
+        action: Action,
+    ) -> CraftState<'_> {
+        let mut state = CraftState::new(context);
+        state.condition = condition;
+        state.execute(&action)
+    }
+
+    #[test]
+    fn quality_conditions() {
+        let (context, _) = setup_1();
+        let quality = |condition| execute_with_condition(&context, condition, BasicTouch).quality;
+        assert_eq!(quality(Condition::Normal), 262);
+        assert_eq!(quality(Condition::Good), 393);
+        assert_eq!(quality(Condition::Excellent), 1048);
+        assert_eq!(quality(Condition::Poor), 131);
+    }
+
+    #[test]
+    fn excellent_is_followed_by_poor() {
+        let (context, _) = setup_1();
+        let state = execute_with_condition(&context, Condition::Excellent, BasicTouch);
+        assert_eq!(state.condition, Condition::Poor);
+        let state = state.execute(&BasicTouch);
+        assert_eq!(state.condition, Condition::Normal);
+    }
+
+    #[test]
+    fn sturdy_halves_durability_cost() {
+        let (context, _) = setup_1();
+        let state = execute_with_condition(&context, Condition::Sturdy, BasicTouch);
+        assert_eq!(state.durability, 75);
+        let state = execute_with_condition(&context, Condition::Sturdy, PrudentTouch);
+        assert_eq!(state.durability, 77);
+    }
+
+    #[test]
+    fn pliant_halves_cp_cost() {
+        let (context, _) = setup_1();
+        let state = execute_with_condition(&context, Condition::Pliant, BasicTouch);
+        assert_eq!(state.cp, 566);
+        let state = execute_with_condition(&context, Condition::Pliant, Manipulation);
+        assert_eq!(state.cp, 527);
+    }
+
+    #[test]
+    fn malleable_increases_progress() {
+        let (context, _) = setup_1();
+        let state = execute_with_condition(&context, Condition::Malleable, BasicSynthesisTraited);
+        assert_eq!(state.progress, 414);
+    }
+
+    #[test]
+    fn primed_extends_buffs() {
+        let (context, _) = setup_1();
+        let state = execute_with_condition(&context, Condition::Primed, Veneration);
+        assert_eq!(state.buffs.veneration, 6);
+        let state = execute_with_condition(&context, Condition::Primed, Manipulation);
+        assert_eq!(state.buffs.manipulation, 10);
+    }
+
+    #[test]
+    fn probabilistic_actions_assume_success() {
+        let actions = vec![HastyTouch, RapidSynthesisTraited];
+        let (context, _) = setup_1();
+        assert_craft(&context, actions, 1150, 262, 60, 575);
+    }
+
+    #[test]
+    fn probabilistic_actions_can_fail() {
+        let (context, _) = setup_1();
+        let state = CraftState::new(&context);
+        let outcomes = state.outcomes(&HastyTouch);
+        let success: f32 = outcomes
+            .iter()
+            .filter(|o| o.success)
+            .map(|o| o.probability)
+            .sum();
+        assert!((success - 0.6).abs() < 1e-6);
+
+        let failure = outcomes.iter().find(|o| !o.success).unwrap();
+        let state = state.execute_stochastic(&HastyTouch, failure);
+        assert_eq!(state.quality, 0);
+        assert_eq!(state.durability, 70);
+        assert_eq!(state.buffs.inner_quiet, 0);
+    }
+
+    #[test]
+    fn centered_increases_success_rate() {
+        let (context, _) = setup_1();
+        let mut state = CraftState::new(&context);
+        state.condition = Condition::Centered;
+        let success: f32 = state
+            .outcomes(&RapidSynthesisTraited)
+            .iter()
+            .filter(|o| o.success)
+            .map(|o| o.probability)
+            .sum();
+        assert!((success - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn strict_moves_exclude_probabilistic_actions() {
+        let (context, _) = setup_1();
+        let state = CraftState::new(&context);
+        assert!(!state.clone_strict().available_moves.contains(HastyTouch));
+        assert!(state
+            .clone_stochastic()
+            .available_moves
+            .contains(HastyTouch));
+    }
+
+    #[test]
+    fn good_condition_actions() {
+        let (context, _) = setup_1();
+        let state = CraftState::new(&context);
+        assert!(!state.available_moves.contains(TricksOfTheTrade));
+        assert!(!state.available_moves.contains(PreciseTouch));
+        assert!(!state.available_moves.contains(IntensiveSynthesis));
+
+        let state = execute_with_condition(&context, Condition::Good, PreciseTouch);
+        assert_eq!(state.quality, 589);
+        assert_eq!(state.buffs.inner_quiet, 2);
+
+        let state = execute_with_condition(&context, Condition::Good, IntensiveSynthesis);
+        assert_eq!(state.progress, 920);
+
+        let mut state = CraftState::new(&context).execute(&BasicTouch);
+        state.condition = Condition::Good;
+        let state = state.execute(&TricksOfTheTrade);
+        assert_eq!(state.cp, 575);
+    }
+
+    #[test]
+    fn final_appraisal_prevents_completion() {
+        let actions = vec![
+            MuscleMemory,
+            FinalAppraisal,
+            GroundworkTraited,
+            GroundworkTraited,
+            GroundworkTraited,
+        ];
+        let (context, _) = setup_1();
+        let (end_state, result) = Simulator::simulate(&context, actions);
+        assert!(result.is_none());
+        assert_eq!(end_state.progress, 3499);
+        assert_eq!(end_state.buffs.final_appraisal, 0);
+        assert_eq!(end_state.cp, 514);
+    }
+
+    #[test]
+    fn final_appraisal_does_not_tick_buffs() {
+        let actions = vec![Manipulation, BasicTouch, FinalAppraisal];
+        let (context, _) = setup_1();
+        let (end_state, _) = Simulator::simulate(&context, actions);
+        assert_eq!(end_state.buffs.manipulation, 7);
+        assert_eq!(end_state.buffs.final_appraisal, 5);
+        assert_eq!(end_state.durability, 75);
+    }
+
+    #[test]
+    fn dawntrail_factors() {
+        let (context, _) = setup_3();
+        assert!((context.progress_factor - 261.0).abs() < f32::EPSILON);
+        assert!((context.quality_factor - 266.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn dawntrail_actions() {
+        let actions = vec![
+            BasicTouch,
+            RefinedTouch,
+            DelicateSynthesisTraited,
+            ImmaculateMend,
+        ];
+        let (context, _) = setup_3();
+        assert_craft(&context, actions.clone(), 391, 903, 80, 434);
+
+        // Refined Touch grants an extra stack of Inner Quiet when comboed
+        let (end_state, _) = Simulator::simulate(&context, actions);
+        assert_eq!(end_state.buffs.inner_quiet, 4);
+    }
+
+    #[test]
+    fn trained_perfection() {
+        let actions = vec![TrainedPerfection, PreparatoryTouch, BasicTouch];
+        let (context, _) = setup_3();
+        let (end_state, _) = Simulator::simulate(&context, actions);
+        assert_eq!(end_state.durability, 70);
+        assert_eq!(end_state.trained_perfection_charges, 0);
+        assert!(!end_state.available_moves.contains(TrainedPerfection));
+    }
+
+    #[test]
+    fn quick_innovation() {
+        let actions = vec![QuickInnovation, BasicTouch];
+        let (context, _) = setup_3();
+        let (end_state, _) = Simulator::simulate(&context, actions);
+        assert_eq!(end_state.quality, 399);
+        assert_eq!(end_state.buffs.innovation, 0);
+        assert!(!end_state.available_moves.contains(QuickInnovation));
+    }
+
+    #[test]
+    fn daring_touch_requires_expedience() {
+        let (context, _) = setup_3();
+        let state = CraftState::new(&context);
+        assert!(!state.available_moves.contains(DaringTouch));
+        let state = state.execute(&HastyTouch);
+        assert!(state.available_moves.contains(DaringTouch));
+        let state = state.execute(&DaringTouch);
+        assert_eq!(state.quality, 704);
+        assert!(!state.available_moves.contains(DaringTouch));
+    }
+
+    /// `setup_1` with a player at a different job level
+    fn setup_level(job_level: u32) -> CraftContext {
+        let player = Player {
+            job_level,
+            ..test_fixtures::player()
+        };
+        let craft_options = CraftOptions {
+            max_steps: 25,
+            ..Default::default()
+        };
+        CraftContext::new(&player, &test_fixtures::recipe(), craft_options)
+    }
+
+    #[test]
+    fn observe_combos_into_advanced_touch_at_level_94() {
+        let actions = vec![Observe, AdvancedTouch];
+        let context = setup_level(93);
+        let (end_state, _) = Simulator::simulate(&context, actions.clone());
+        assert_eq!(end_state.cp, 575 - 7 - 46);
+        let context = setup_level(94);
+        let (end_state, _) = Simulator::simulate(&context, actions);
+        assert_eq!(end_state.cp, 575 - 7 - 18);
+
+        // strict searches can follow Observe with the combo
+        let state = CraftState::new_strict(&context).execute_strict(&Observe);
+        assert!(state.available_moves.contains(AdvancedTouch));
+    }
+
+    #[test]
+    fn refreshing_manipulation_restores_nothing_at_level_96() {
+        let actions = vec![Manipulation, BasicTouch, Manipulation];
+        let context = setup_level(95);
+        let (end_state, _) = Simulator::simulate(&context, actions.clone());
+        assert_eq!(end_state.durability, 80);
+        let context = setup_level(96);
+        let (end_state, _) = Simulator::simulate(&context, actions);
+        assert_eq!(end_state.durability, 75);
+    }
+
+    #[test]
+    fn rotation_should_not_panic_3() {
+        let actions = vec![
+            MuscleMemory,
+            Manipulation,
+            Veneration,
+            WasteNotII,
+            GroundworkTraited,
+            GroundworkTraited,
+            RefinedTouch,
+            Innovation,
+            PreparatoryTouch,
+            PreparatoryTouch,
+            QuickInnovation,
+            PreparatoryTouch,
+            TrainedPerfection,
+            PreparatoryTouch,
+            ImmaculateMend,
+            GreatStrides,
+            Innovation,
+            TrainedFinesse,
+            GreatStrides,
+            ByregotsBlessing,
+            CarefulSynthesisTraited,
+        ];
+        let (context, _) = setup_3();
+        Simulator::simulate(&context, actions);
+    }
+
+    #[test]
+    fn specialist_actions_require_specialist() {
+        let (context, _) = setup_1();
+        assert!(!context.action_pool.contains(CarefulObservation));
+        assert!(!context.action_pool.contains(HeartAndSoul));
+
+        let (context, _) = setup_3();
+        assert!(context.action_pool.contains(CarefulObservation));
+        assert!(context.action_pool.contains(HeartAndSoul));
+        assert!(context.action_pool.contains(QuickInnovation));
+    }
+
+    #[test]
+    fn heart_and_soul() {
+        let (context, _) = setup_3();
+        let state = CraftState::new(&context).execute(&HeartAndSoul);
+        assert!(state.buffs.heart_and_soul);
+        assert_eq!(state.heart_and_soul_charges, 0);
+        assert!(!state.available_moves.contains(HeartAndSoul));
+        assert!(state.available_moves.contains(PreciseTouch));
+
+        let state = state.execute(&PreciseTouch);
+        assert!(!state.buffs.heart_and_soul);
+        assert_eq!(state.buffs.inner_quiet, 2);
+        assert!(!state.available_moves.contains(IntensiveSynthesis));
+    }
+
+    #[test]
+    fn heart_and_soul_is_kept_in_good_condition() {
+        let (context, _) = setup_3();
+        let mut state = CraftState::new(&context).execute(&HeartAndSoul);
+        state.condition = Condition::Good;
+        let state = state.execute(&PreciseTouch);
+        assert!(state.buffs.heart_and_soul);
+    }
+
+    #[test]
+    fn careful_observation() {
+        let (context, _) = setup_3();
+        let mut state = CraftState::new(&context).execute(&Veneration);
+        state.condition = Condition::Good;
+
+        let outcomes = state.outcomes(&CarefulObservation);
+        assert_eq!(
+            outcomes.len(),
+            state.condition.next_conditions(&context).len()
+        );
+
+        let state = state.execute(&CarefulObservation);
+        assert_eq!(state.careful_observation_charges, 2);
+        assert_eq!(state.buffs.veneration, 4);
+        assert_eq!(state.condition, Condition::Normal);
+
+        let state = state
+            .execute(&CarefulObservation)
+            .execute(&CarefulObservation);
+        assert!(!state.available_moves.contains(CarefulObservation));
+        assert!(!state
+            .clone_strict()
+            .available_moves
+            .contains(CarefulObservation));
+
+        assert_eq!(
+            CarefulObservation.macro_text(),
+            "/ac \"Careful Observation\" <wait.3>"
+        );
+    }
+
     #[test]
     fn rotation_should_not_panic_1() {
         let actions = vec![
@@ -544,9 +1927,630 @@ mod tests {
         Simulator::simulate(&context, actions);
     }
 
+    fn setup_expert() -> (CraftContext, SearchOptions) {
+        let recipe = Recipe {
+            recipe_level: 590,
+            job_level: 90,
+            stars: 3,
+            progress: 5060,
+            quality: 12628,
+            durability: 55,
+            progress_div: 130,
+            progress_mod: 70,
+            quality_div: 115,
+            quality_mod: 60,
+            is_expert: true,
+            conditions_flag: 483,
+        };
+        let player = Player::new(90, 4000, 3900, 700);
+        let craft_options = CraftOptions {
+            max_steps: 30,
+            ..Default::default()
+        };
+        let context = CraftContext::new(&player, &recipe, craft_options);
+        let options = SearchOptions {
+            iterations: 5_000,
+            rng_seed: Some(42),
+            ..Default::default()
+        };
+        (context, options)
+    }
+
+    #[test]
+    fn search_policy_covers_outcomes() {
+        let (context, options) = setup_expert();
+        let state = CraftState::new(&context);
+        let policy = Simulator::search_policy(&state, options).unwrap();
+
+        assert!(state
+            .clone_stochastic()
+            .available_moves
+            .contains(policy.action));
+        assert!(policy.expected_score > 0.0);
+
+        let outcomes: Vec<Outcome> = policy.next_actions.iter().map(|(o, _)| *o).collect();
+        assert_eq!(outcomes, state.outcomes(&policy.action));
+    }
+
+    #[test]
+    fn search_policy_is_deterministic_for_seed() {
+        let (context, options) = setup_expert();
+        let state = CraftState::new(&context);
+        let a = Simulator::search_policy(&state, options.clone()).unwrap();
+        let b = Simulator::search_policy(&state, options).unwrap();
+        assert_eq!(a.action, b.action);
+        assert_eq!(a.next_actions, b.next_actions);
+    }
+
+    #[test]
+    fn search_policy_ends_with_craft() {
+        let (context, options) = setup_1();
+        let (state, _) = Simulator::simulate(
+            &context,
+            vec![
+                MuscleMemory,
+                GroundworkTraited,
+                GroundworkTraited,
+                GroundworkTraited,
+            ],
+        );
+        assert!(Simulator::search_policy(&state, options).is_none());
+    }
+
+    #[test]
+    fn stochastic_search_weighs_failed_finishing_actions() {
+        // the last step, a single Groundwork or Careful Synthesis away from
+        // finishing
+        let context = CraftContext::new(
+            &test_fixtures::player(),
+            &test_fixtures::recipe(),
+            CraftOptions {
+                max_steps: 15,
+                ..Default::default()
+            },
+        );
+        let (state, _) = Simulator::simulate(
+            &context,
+            vec![
+                MuscleMemory,
+                CarefulSynthesisTraited,
+                CarefulSynthesisTraited,
+                CarefulSynthesisTraited,
+                CarefulSynthesisTraited,
+                MastersMend,
+                Innovation,
+                PreparatoryTouch,
+                PreparatoryTouch,
+                MastersMend,
+                BasicTouch,
+                StandardTouch,
+                CarefulSynthesisTraited,
+            ],
+        );
+        let options = SearchOptions {
+            iterations: 5_000,
+            rng_seed: Some(0),
+            ..Default::default()
+        };
+        let mut sim = Simulator::from_state(state.clone_stochastic(), options);
+        sim.stochastic = true;
+        sim.search(0);
+
+        let average_score = |action| {
+            let index = sim.child_by_action(0, action).unwrap();
+            let state = &sim.tree.get(index).state;
+            state.score_sum / state.visits
+        };
+        // Rapid Synthesis would finish with the most CP left, but it fails
+        // half the time, and there's no step left to try again
+        let rapid_synthesis = average_score(RapidSynthesisTraited);
+        assert!(rapid_synthesis < average_score(GroundworkTraited));
+        assert!(rapid_synthesis < average_score(CarefulSynthesisTraited));
+    }
+
     #[test]
     fn search_should_not_panic() {
         let (context, options) = setup_2();
-        Simulator::search_oneshot(&context, vec![], options);
+        Simulator::search_oneshot(&context, vec![], options).unwrap();
+    }
+
+    #[test]
+    fn parallel_search_merges_trees() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 1_000,
+            threads: Some(4),
+            ..options
+        };
+        let mut sim = Simulator::from_context(&context, options);
+        sim.search(0);
+
+        let root = sim.tree.get(0);
+        assert!((root.state.visits - 1_000.0).abs() < f32::EPSILON);
+
+        // each action is expanded at most once, and is no longer available
+        let actions: Vec<Action> = root
+            .children
+            .iter()
+            .map(|&index| sim.tree.get(index).state.action.unwrap())
+            .collect();
+        let action_set: ActionSet = actions.iter().copied().collect();
+        assert_eq!(action_set.len(), actions.len());
+        assert!(!root.state.available_moves.intersects(action_set));
+    }
+
+    #[test]
+    fn parallel_search_is_deterministic_for_seed() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 2_000,
+            threads: Some(3),
+            ..options
+        };
+        let (a, _) = Simulator::search_oneshot(&context, vec![], options.clone()).unwrap();
+        let (b, _) = Simulator::search_oneshot(&context, vec![], options).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn parallel_search_forks_have_root_moves() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 2_000,
+            threads: Some(2),
+            ..options
+        };
+        let mut search = AnytimeSearch::new(&context, vec![], options).unwrap();
+        search.run(1_000);
+        let first = search.stats().rollouts;
+        search.run(1_000);
+        let second = search.stats().rollouts;
+
+        // rollouts in the second chunk start from the root's moves too, rather
+        // than the ones left over after the first chunk expanded them
+        let invalid = second.invalid_action_failure - first.invalid_action_failure;
+        assert_eq!(second.total() - first.total(), 1_000);
+        assert!(invalid < 500);
+    }
+
+    #[test]
+    fn parallel_search_policy_covers_outcomes() {
+        let (context, options) = setup_expert();
+        let options = SearchOptions {
+            threads: Some(2),
+            ..options
+        };
+        let state = CraftState::new(&context);
+        let policy = Simulator::search_policy(&state, options).unwrap();
+
+        let outcomes: Vec<Outcome> = policy.next_actions.iter().map(|(o, _)| *o).collect();
+        assert_eq!(outcomes, state.outcomes(&policy.action));
+    }
+
+    #[test]
+    fn transpositions_share_nodes() {
+        let (context, options) = setup_1();
+        let mut sim = Simulator::from_state(CraftState::new_strict(&context), options);
+
+        let mut path_a = vec![0];
+        sim.execute_actions_strict(
+            &mut path_a,
+            vec![BasicSynthesisTraited, CarefulSynthesisTraited],
+        );
+        let mut path_b = vec![0];
+        sim.execute_actions_strict(
+            &mut path_b,
+            vec![CarefulSynthesisTraited, BasicSynthesisTraited],
+        );
+
+        assert_eq!(path_a.len(), 3);
+        assert_eq!(path_b.len(), 3);
+        assert_ne!(path_a[1], path_b[1]);
+        assert_eq!(path_a[2], path_b[2]);
+        assert_eq!(sim.tree.nodes.len(), 4);
+        assert_eq!(
+            sim.edge_action(path_b[1], path_b[2]),
+            Some(BasicSynthesisTraited)
+        );
+        assert_eq!(
+            sim.edge_action(path_a[1], path_a[2]),
+            Some(CarefulSynthesisTraited)
+        );
+    }
+
+    #[test]
+    fn solution_matches_simulation() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 5_000,
+            ..options
+        };
+        let (actions, state) = Simulator::search_oneshot(&context, vec![], options).unwrap();
+        let (simulated, _) = Simulator::simulate(&context, actions);
+        assert_eq!(simulated.progress, state.progress);
+        assert_eq!(simulated.quality, state.quality);
+        assert_eq!(simulated.durability, state.durability);
+        assert_eq!(simulated.cp, state.cp);
+    }
+
+    #[test]
+    fn oneshot_continues_from_action_history() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 2_000,
+            ..options
+        };
+        let history = vec![MuscleMemory, Veneration];
+        let (actions, state) =
+            Simulator::search_oneshot(&context, history.clone(), options).unwrap();
+        assert_eq!(actions[..2], history[..]);
+
+        let (simulated, _) = Simulator::simulate(&context, actions);
+        assert_eq!(simulated.progress, state.progress);
+        assert_eq!(simulated.quality, state.quality);
+    }
+
+    #[test]
+    fn oneshot_searches_skip_actions_that_can_fail() {
+        let (context, options) = setup_1();
+        let can_fail = |actions: &[Action]| {
+            actions
+                .iter()
+                .any(|action| action.attributes().success_rate.is_some())
+        };
+        for rng_seed in 0..10 {
+            let options = SearchOptions {
+                iterations: 3_000,
+                rng_seed: Some(rng_seed),
+                ..options.clone()
+            };
+            let (actions, _) =
+                Simulator::search_oneshot(&context, vec![], options.clone()).unwrap();
+            assert!(!can_fail(&actions), "{actions:?}");
+
+            let mut search = AnytimeSearch::new(&context, vec![], options).unwrap();
+            search.run(3_000);
+            let (actions, _) = search.best();
+            assert!(!can_fail(&actions), "{actions:?}");
+        }
+    }
+
+    #[test]
+    fn oneshot_rejects_invalid_action_history() {
+        let (context, options) = setup_1();
+        let result = Simulator::search_oneshot(&context, vec![Veneration, MuscleMemory], options);
+        assert!(matches!(result, Err(CraftResult::InvalidActionFailure)));
+    }
+
+    #[test]
+    fn time_limit_stops_search() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 1_000_000,
+            time_limit: Some(Duration::ZERO),
+            ..options
+        };
+        let mut search = AnytimeSearch::new(&context, vec![], options.clone()).unwrap();
+        assert!(!search.run(1_000_000));
+        assert_eq!(search.iterations_run(), 1);
+
+        let (actions, _) = Simulator::search_stepwise(&context, vec![], options, None);
+        let (_, result) = Simulator::simulate(&context, actions);
+        assert!(result.is_some());
+    }
+
+    #[test]
+    fn keeps_searching_after_reaching_quality_target() {
+        let (_, options) = setup_1();
+        let craft_options = CraftOptions {
+            max_steps: 25,
+            quality_target: Some(3_000),
+            ..Default::default()
+        };
+        let context = CraftContext::new(
+            &test_fixtures::player(),
+            &test_fixtures::recipe(),
+            craft_options,
+        );
+        let options = SearchOptions {
+            iterations: 20_000,
+            stop_at_quality_target: Some(true),
+            ..options
+        };
+        let mut search = AnytimeSearch::new(&context, vec![], options.clone()).unwrap();
+        let mut first_target_step = None;
+        while search.run(1_000) {
+            let (_, state) = search.best();
+            if state.quality >= context.quality_target {
+                first_target_step.get_or_insert(state.step);
+            }
+        }
+        // no rotation is proven to be the shortest, so the search runs out
+        let (_, state) = search.best();
+        assert_eq!(search.iterations_run(), u64::from(options.iterations));
+        assert!(state.quality >= context.quality_target);
+        assert!(matches!(
+            state.check_result(),
+            Some(CraftResult::Finished(_))
+        ));
+        assert!(state.step < first_target_step.unwrap());
+    }
+
+    #[test]
+    fn stops_at_shortest_quality_target_rotation() {
+        let recipe = Recipe {
+            progress: 200,
+            ..test_fixtures::recipe()
+        };
+        let craft_options = CraftOptions {
+            max_steps: 25,
+            // low enough that finishing with no quality yet isn't pruned
+            quality_target: Some(4),
+            ..Default::default()
+        };
+        let context = CraftContext::new(&test_fixtures::player(), &recipe, craft_options);
+        let options = SearchOptions {
+            iterations: 50_000,
+            rng_seed: Some(0),
+            stop_at_quality_target: Some(true),
+            ..Default::default()
+        };
+        let mut search = AnytimeSearch::new(&context, vec![], options.clone()).unwrap();
+        search.run(options.iterations);
+        let (actions, state) = search.best();
+        // Delicate Synthesis finishes the craft at the target in one step,
+        // which is as short as a rotation can be
+        assert!(search.iterations_run() < u64::from(options.iterations));
+        assert_eq!(actions, vec![DelicateSynthesis]);
+        assert!(state.quality >= context.quality_target);
+    }
+
+    #[test]
+    fn anytime_search_runs_in_chunks() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 1_000,
+            ..options
+        };
+        let mut search = AnytimeSearch::new(&context, vec![], options.clone()).unwrap();
+        let mut best_score = 0.0;
+        while search.run(300) {
+            let (_, state) = search.best();
+            assert!(state.max_score >= best_score);
+            best_score = state.max_score;
+        }
+        assert_eq!(search.iterations_run(), 1_000);
+
+        let (oneshot, _) = Simulator::search_oneshot(&context, vec![], options).unwrap();
+        assert_eq!(search.best().0, oneshot);
+    }
+
+    #[test]
+    fn reports_progress() {
+        let (context, options) = setup_1();
+        let reports = Arc::new(Mutex::new(vec![]));
+        let options = SearchOptions {
+            iterations: 1_000,
+            progress_interval: Some(250),
+            progress_callback: Some(ProgressCallback::new({
+                let reports = reports.clone();
+                move |progress: &SearchProgress| reports.lock().unwrap().push(progress.clone())
+            })),
+            ..options
+        };
+        Simulator::search_oneshot(&context, vec![], options).unwrap();
+
+        let reports = reports.lock().unwrap();
+        let iterations: Vec<u64> = reports.iter().map(|p| p.iterations).collect();
+        assert_eq!(iterations, vec![250, 500, 750, 1_000]);
+        assert!(reports
+            .windows(2)
+            .all(|w| w[0].best_score <= w[1].best_score));
+        assert!(reports.windows(2).all(|w| w[0].nodes <= w[1].nodes));
+        assert!(!reports[3].best_actions.is_empty());
+    }
+
+    #[test]
+    fn cancellation_stops_search() {
+        let (context, options) = setup_1();
+        let token = CancellationToken::new();
+        let options = SearchOptions {
+            iterations: 1_000_000,
+            progress_interval: Some(100),
+            progress_callback: Some(ProgressCallback::new({
+                let token = token.clone();
+                move |_: &SearchProgress| token.cancel()
+            })),
+            cancellation_token: Some(token.clone()),
+            ..options
+        };
+        let mut search = AnytimeSearch::new(&context, vec![], options.clone()).unwrap();
+        assert!(!search.run(1_000_000));
+        assert_eq!(search.iterations_run(), 100);
+
+        // a cancelled stepwise search returns the actions picked so far
+        let history = vec![MuscleMemory];
+        let (actions, state) = Simulator::search_stepwise(&context, history.clone(), options, None);
+        assert_eq!(actions, history);
+        assert!(state.check_result().is_none());
+    }
+
+    #[test]
+    fn records_search_stats() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 1_000,
+            progress_interval: Some(250),
+            ..options
+        };
+        let mut search = AnytimeSearch::new(&context, vec![], options.clone()).unwrap();
+        search.run(options.iterations);
+        let stats = search.stats();
+
+        assert_eq!(stats.iterations, 1_000);
+        assert_eq!(stats.rollouts.total(), 1_000);
+        assert_eq!(stats.best_score_history.len(), 4);
+        assert!(stats.best_score_history.windows(2).all(|w| w[0] <= w[1]));
+        assert!(stats.nodes > 1);
+        assert!(stats.max_depth > 0);
+        assert!(stats.memory_bytes > 0);
+    }
+
+    #[test]
+    fn public_searches_return_stats() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 500,
+            ..options
+        };
+        let (_, _, oneshot_stats) =
+            Simulator::search_oneshot_with_stats(&context, vec![], options.clone()).unwrap();
+        assert_eq!(oneshot_stats.iterations, 500);
+
+        let state = CraftState::new(&context);
+        let (policy, policy_stats) = Simulator::search_policy_with_stats(&state, options);
+        assert!(policy.is_some());
+        assert_eq!(policy_stats.rollouts.total(), 500);
+    }
+
+    #[test]
+    fn parallel_search_combines_stats() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 1_000,
+            progress_interval: Some(100),
+            threads: Some(4),
+            ..options
+        };
+        let mut search = AnytimeSearch::new(&context, vec![], options.clone()).unwrap();
+        search.run(options.iterations);
+        let stats = search.stats();
+
+        assert_eq!(stats.rollouts.total(), 1_000);
+        // 250 iterations in each thread
+        assert_eq!(stats.best_score_history.len(), 2);
+    }
+
+    #[test]
+    fn exports_filtered_tree() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 2_000,
+            ..options
+        };
+        let mut search = AnytimeSearch::new(&context, vec![], options.clone()).unwrap();
+        search.run(options.iterations);
+
+        let export_options = TreeExportOptions {
+            max_depth: Some(3),
+            min_visits: 20,
+            max_children: Some(2),
+        };
+        let export = search.export_tree(&export_options);
+        assert!(export.nodes.len() > 1);
+        assert!(export.nodes.iter().all(|node| node.depth <= 3));
+        assert!(export.nodes[1..].iter().all(|node| node.visits >= 20.0));
+        for node in &export.nodes[1..] {
+            assert!(export.edges.iter().any(|edge| edge.child == node.id));
+        }
+        for node in &export.nodes {
+            let children = export.edges.iter().filter(|edge| edge.parent == node.id);
+            assert!(children.count() <= 2);
+        }
+        assert!(export.edges.iter().all(|edge| edge.action.is_some()));
+
+        let unfiltered = search.export_tree(&TreeExportOptions {
+            max_depth: None,
+            min_visits: 0,
+            max_children: None,
+        });
+        assert_eq!(unfiltered.nodes.len(), search.stats().nodes);
+    }
+
+    #[test]
+    fn stepwise_search_reuses_tree() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 1_000,
+            ..options
+        };
+        let (actions, state, search_stats) =
+            Simulator::search_stepwise_with_stats(&context, vec![], options, None);
+        let steps = u64::try_from(actions.len()).unwrap();
+        assert!(search_stats.iterations < steps * 1_000);
+
+        let (simulated, _) = Simulator::simulate(&context, actions);
+        assert_eq!(simulated.progress, state.progress);
+        assert_eq!(simulated.quality, state.quality);
+        assert_eq!(simulated.cp, state.cp);
+    }
+
+    #[test]
+    fn parallel_stepwise_search_reroots_with_fresh_moves() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 1_000,
+            threads: Some(2),
+            ..options
+        };
+        let (actions, state, search_stats) =
+            Simulator::search_stepwise_with_stats(&context, vec![], options, None);
+        let rollouts = search_stats.rollouts;
+        assert!(rollouts.invalid_action_failure < rollouts.total() / 2);
+
+        let (simulated, result) = Simulator::simulate(&context, actions);
+        assert_eq!(simulated.progress, state.progress);
+        assert!(matches!(result, Some(CraftResult::Finished(_))));
+    }
+
+    #[test]
+    fn reroot_keeps_transposition_edges() {
+        let (context, options) = setup_1();
+        let options = SearchOptions {
+            iterations: 2_000,
+            ..options
+        };
+        let mut sim = Simulator::from_context(&context, options);
+        sim.search(0);
+
+        let child_index = sim.tree.get(0).children[0];
+        let child_state = sim.tree.get(child_index).state.clone();
+        sim.reroot(child_index);
+
+        assert_eq!(sim.tree.get(0).state.key(), child_state.key());
+        assert_eq!(sim.transpositions.get(&child_state.key()), Some(&0));
+        for (index, node) in sim.tree.nodes.iter().enumerate() {
+            assert_eq!(sim.transpositions.get(&node.state.key()), Some(&index));
+            for &child in &node.children {
+                assert!(sim.edge_action(index, child).is_some());
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_search_options_through_json() {
+        let options = SearchOptions {
+            iterations: 500,
+            rng_seed: Some(3),
+            time_limit: Some(Duration::from_millis(1_500)),
+            progress_callback: Some(ProgressCallback::new(|_: &SearchProgress| {})),
+            cancellation_token: Some(CancellationToken::new()),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&options).unwrap();
+        assert!(!json.contains("progress_callback"));
+
+        let restored: SearchOptions = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.iterations, 500);
+        assert_eq!(restored.rng_seed, Some(3));
+        assert_eq!(restored.time_limit, Some(Duration::from_millis(1_500)));
+        assert!(restored.progress_callback.is_none());
+        assert!(restored.cancellation_token.is_none());
+
+        // missing options fall back to defaults
+        let restored: SearchOptions = serde_json::from_str(r#"{"iterations":20}"#).unwrap();
+        assert_eq!(restored.iterations, 20);
+        assert_eq!(restored.progress_interval, Some(1_000));
     }
 }
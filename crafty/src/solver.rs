@@ -0,0 +1,177 @@
+use crate::{
+    Action, AnytimeSearch, BeamOptions, BeamSearch, BestFirstOptions, BestFirstSearch,
+    CraftContext, CraftResult, CraftState, ExactOptions, ExactSearch, SearchOptions, SearchStats,
+    Simulator,
+};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct Solution<'a> {
+    /// The action history followed by the actions picked by the solver
+    pub actions: Vec<Action>,
+    /// The state after using every action
+    pub state: CraftState<'a>,
+    /// The score of the finished craft, or 0.0 if the craft wasn't finished
+    pub score: f32,
+    /// Number of iterations or states searched, depending on the solver
+    pub iterations: u64,
+    /// How long the solver took
+    pub elapsed: Duration,
+    /// Diagnostics from tree searches. None for other solvers
+    pub stats: Option<SearchStats>,
+}
+
+impl<'a> Solution<'a> {
+    fn timed(solve: impl FnOnce() -> (Vec<Action>, CraftState<'a>, u64)) -> Self {
+        let started = Instant::now();
+        let (actions, state, iterations) = solve();
+        let score = match state.check_result() {
+            Some(CraftResult::Finished(score)) => score,
+            _ => 0.0,
+        };
+        Self {
+            actions,
+            state,
+            score,
+            iterations,
+            elapsed: started.elapsed(),
+            stats: None,
+        }
+    }
+
+    fn with_stats(self, stats: Option<SearchStats>) -> Self {
+        Self { stats, ..self }
+    }
+}
+
+/// A strategy for finding a rotation. Each solver holds its own options, so
+/// solvers can be picked at runtime as a `Box<dyn Solver>`.
+pub trait Solver {
+    /// Searches for a rotation after `action_history`
+    fn solve<'a>(&self, context: &'a CraftContext, action_history: Vec<Action>) -> Solution<'a>;
+}
+
+/// Monte Carlo tree search, either as one large search or as a new search
+/// for every step
+#[derive(Clone)]
+pub enum MctsSolver {
+    /// See `AnytimeSearch`
+    Oneshot(SearchOptions),
+    /// See `Simulator::search_stepwise`
+    Stepwise(SearchOptions),
+}
+
+impl Solver for MctsSolver {
+    fn solve<'a>(&self, context: &'a CraftContext, action_history: Vec<Action>) -> Solution<'a> {
+        let mut search_stats = None;
+        let solution = Solution::timed(|| match self {
+            Self::Oneshot(options) => {
+                let search = AnytimeSearch::new(context, action_history.clone(), options.clone());
+                if let Ok(mut search) = search {
+                    search.run(options.iterations);
+                    let (actions, state) = search.best();
+                    search_stats = Some(search.stats());
+                    (actions, state, search.iterations_run())
+                } else {
+                    // the action history already ended the craft
+                    let (state, _) = Simulator::simulate(context, action_history.clone());
+                    (action_history, state, 0)
+                }
+            }
+            Self::Stepwise(options) => {
+                let (actions, state, stepwise_stats) = Simulator::search_stepwise_with_stats(
+                    context,
+                    action_history,
+                    options.clone(),
+                    None,
+                );
+                let iterations = stepwise_stats.iterations;
+                search_stats = Some(stepwise_stats);
+                (actions, state, iterations)
+            }
+        });
+        solution.with_stats(search_stats)
+    }
+}
+
+/// See `ExactSearch::solve`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExactSolver(pub ExactOptions);
+
+impl Solver for ExactSolver {
+    fn solve<'a>(&self, context: &'a CraftContext, action_history: Vec<Action>) -> Solution<'a> {
+        Solution::timed(|| {
+            let solution = ExactSearch::solve(context, action_history, self.0);
+            (solution.actions, solution.state, solution.states_searched)
+        })
+    }
+}
+
+/// See `BeamSearch::solve`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BeamSolver(pub BeamOptions);
+
+impl Solver for BeamSolver {
+    fn solve<'a>(&self, context: &'a CraftContext, action_history: Vec<Action>) -> Solution<'a> {
+        Solution::timed(|| BeamSearch::search(context, action_history, self.0))
+    }
+}
+
+/// See `BestFirstSearch::solve`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BestFirstSolver(pub BestFirstOptions);
+
+impl Solver for BestFirstSolver {
+    fn solve<'a>(&self, context: &'a CraftContext, action_history: Vec<Action>) -> Solution<'a> {
+        Solution::timed(|| BestFirstSearch::search(context, action_history, self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::low_level_context;
+
+    fn solvers() -> Vec<Box<dyn Solver>> {
+        let search_options = SearchOptions {
+            iterations: 5_000,
+            rng_seed: Some(0),
+            ..Default::default()
+        };
+        vec![
+            Box::new(MctsSolver::Oneshot(search_options.clone())),
+            Box::new(MctsSolver::Stepwise(search_options)),
+            Box::new(ExactSolver::default()),
+            Box::new(BeamSolver(BeamOptions { width: 20 })),
+            Box::new(BestFirstSolver(BestFirstOptions {
+                max_expansions: 1_000,
+            })),
+        ]
+    }
+
+    #[test]
+    fn solvers_finish_craft() {
+        let context = low_level_context(6);
+        for solver in solvers() {
+            let solution = solver.solve(&context, vec![]);
+            assert!(solution.score > 0.0);
+            assert!(solution.iterations > 0);
+            if let Some(stats) = &solution.stats {
+                assert_eq!(stats.iterations, solution.iterations);
+            }
+
+            let (state, result) = Simulator::simulate(&context, solution.actions);
+            assert!(matches!(result, Some(CraftResult::Finished(_))));
+            assert_eq!(state.quality, solution.state.quality);
+        }
+    }
+
+    #[test]
+    fn unfinished_solution_scores_zero() {
+        let context = low_level_context(6);
+        let solution =
+            BestFirstSolver(BestFirstOptions { max_expansions: 1 }).solve(&context, vec![]);
+        assert!(solution.state.progress < context.progress_target);
+        assert!(solution.score.abs() < f32::EPSILON);
+    }
+}
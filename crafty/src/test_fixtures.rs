@@ -0,0 +1,59 @@
+// The recipes and players most tests craft with
+
+use crate::{CraftContext, CraftOptions, Player, Recipe};
+
+/// A level 90 recipe with no stars
+pub(crate) fn recipe() -> Recipe {
+    Recipe {
+        recipe_level: 560,
+        job_level: 90,
+        stars: 0,
+        progress: 3500,
+        quality: 7200,
+        durability: 80,
+        progress_div: 130,
+        progress_mod: 90,
+        quality_div: 115,
+        quality_mod: 80,
+        is_expert: false,
+        conditions_flag: 15,
+    }
+}
+
+pub(crate) fn player() -> Player {
+    Player::new(90, 3304, 3374, 575)
+}
+
+/// `recipe` crafted by `player` in at most 25 steps
+pub(crate) fn context() -> CraftContext {
+    let craft_options = CraftOptions {
+        max_steps: 25,
+        ..Default::default()
+    };
+    CraftContext::new(&player(), &recipe(), craft_options)
+}
+
+/// A level 25 recipe crafted by a level 30 player in at most `max_steps`
+/// steps, small enough to search exhaustively
+pub(crate) fn low_level_context(max_steps: u8) -> CraftContext {
+    let recipe = Recipe {
+        recipe_level: 25,
+        job_level: 25,
+        stars: 0,
+        progress: 150,
+        quality: 1200,
+        durability: 40,
+        progress_div: 50,
+        progress_mod: 100,
+        quality_div: 30,
+        quality_mod: 100,
+        is_expert: false,
+        conditions_flag: 15,
+    };
+    let player = Player::new(30, 250, 200, 250);
+    let craft_options = CraftOptions {
+        max_steps,
+        ..Default::default()
+    };
+    CraftContext::new(&player, &recipe, craft_options)
+}
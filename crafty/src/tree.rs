@@ -1,3 +1,5 @@
+use std::mem;
+
 #[derive(Debug)]
 pub struct Arena<T> {
     pub nodes: Vec<Node<T>>,
@@ -29,6 +31,59 @@ impl<T> Arena<T> {
         index
     }
 
+    /// Adds an existing node as a child of another node. The node keeps its
+    /// original parent.
+    pub fn link(&mut self, parent_index: usize, child_index: usize) {
+        self.get_mut(parent_index).children.push(child_index);
+    }
+
+    /// Approximate heap memory used by the nodes, in bytes. Memory owned by
+    /// the states themselves isn't counted.
+    pub fn memory_usage(&self) -> usize {
+        let children: usize = self
+            .nodes
+            .iter()
+            .map(|node| node.children.capacity() * mem::size_of::<usize>())
+            .sum();
+        self.nodes.capacity() * mem::size_of::<Node<T>>() + children
+    }
+
+    /// Makes a node the new root, dropping every node that can't be reached
+    /// from it. The remaining nodes are moved to the front of the arena, and
+    /// the new index of each old node is returned, or None if it was dropped.
+    /// Nodes whose parent was dropped are left without one.
+    pub fn reroot(&mut self, root_index: usize) -> Vec<Option<usize>> {
+        let mut mapping = vec![None; self.nodes.len()];
+        let mut order = vec![root_index];
+        mapping[root_index] = Some(0);
+        let mut next = 0;
+        while next < order.len() {
+            for &child in &self.get(order[next]).children {
+                if mapping[child].is_none() {
+                    mapping[child] = Some(order.len());
+                    order.push(child);
+                }
+            }
+            next += 1;
+        }
+
+        let mut old_nodes: Vec<Option<Node<T>>> = self.nodes.drain(..).map(Some).collect();
+        self.nodes = order
+            .iter()
+            .map(|&old_index| {
+                let mut node = old_nodes[old_index].take().unwrap();
+                node.index = mapping[old_index].unwrap();
+                node.parent = node.parent.and_then(|parent| mapping[parent]);
+                for child in &mut node.children {
+                    *child = mapping[*child].unwrap();
+                }
+                node
+            })
+            .collect();
+        self.nodes[0].parent = None;
+        mapping
+    }
+
     pub fn get(&self, index: usize) -> &Node<T> {
         self.nodes.get(index).unwrap()
     }
@@ -41,6 +96,7 @@ impl<T> Arena<T> {
 #[derive(Debug)]
 pub struct Node<T> {
     pub parent: Option<usize>,
+    #[allow(dead_code)]
     pub index: usize,
     pub children: Vec<usize>,
     pub state: T,
@@ -70,4 +126,46 @@ mod tests {
         assert_eq!(arena.get(index_b).state, "b");
         assert_eq!(arena.get(0).children.len(), 1);
     }
+
+    #[test]
+    fn links_existing_nodes() {
+        let mut arena = Arena::new("a");
+        let index_b = arena.insert(0, "b");
+        let index_c = arena.insert(0, "c");
+
+        arena.link(index_b, index_c);
+
+        assert_eq!(arena.get(index_b).children, vec![index_c]);
+        assert_eq!(arena.get(index_c).parent, Some(0));
+    }
+
+    #[test]
+    fn memory_usage_grows_with_nodes() {
+        let mut arena = Arena::new(0_u64);
+        let initial = arena.memory_usage();
+        for _ in 0..100 {
+            arena.insert(0, 0);
+        }
+        assert!(arena.memory_usage() >= initial + 100 * std::mem::size_of::<u64>());
+    }
+
+    #[test]
+    fn reroots_to_subtree() {
+        let mut arena = Arena::new("a");
+        let index_b = arena.insert(0, "b");
+        let index_c = arena.insert(0, "c");
+        let index_d = arena.insert(index_c, "d");
+        let index_e = arena.insert(index_b, "e");
+        arena.link(index_c, index_e);
+
+        let mapping = arena.reroot(index_c);
+
+        assert_eq!(mapping, vec![None, None, Some(0), Some(1), Some(2)]);
+        assert_eq!(arena.nodes.len(), 3);
+        assert_eq!(arena.get(0).state, "c");
+        assert_eq!(arena.get(0).parent, None);
+        assert_eq!(arena.get(0).children, vec![1, 2]);
+        assert_eq!(arena.get(mapping[index_d].unwrap()).parent, Some(0));
+        assert_eq!(arena.get(mapping[index_e].unwrap()).parent, None);
+    }
 }
@@ -0,0 +1,168 @@
+use crate::Action;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TreeExportOptions {
+    /// Nodes deeper than this below the starting node are left out. Unlimited
+    /// if None
+    pub max_depth: Option<usize>,
+    /// Nodes visited fewer times than this are left out, along with everything
+    /// below them
+    pub min_visits: u32,
+    /// Only the most visited children of each node are kept. Unlimited if None
+    pub max_children: Option<usize>,
+}
+
+impl Default for TreeExportOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: Some(4),
+            min_visits: 1,
+            max_children: Some(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportedNode {
+    /// Index of the node in the search tree
+    pub id: usize,
+    /// Number of nodes between this node and the starting node
+    pub depth: usize,
+    pub visits: f32,
+    pub average_score: f32,
+    pub max_score: f32,
+    pub progress: u32,
+    pub quality: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportedEdge {
+    pub parent: usize,
+    pub child: usize,
+    pub action: Option<Action>,
+}
+
+/// A filtered copy of a search tree. Nodes that were reached with different
+/// action orders appear once, with an edge from each parent.
+#[derive(Debug, Clone, Default)]
+pub struct TreeExport {
+    pub nodes: Vec<ExportedNode>,
+    pub edges: Vec<ExportedEdge>,
+}
+
+impl TreeExport {
+    /// Renders the tree as a Graphviz DOT graph
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph search {\n    node [shape=box];\n");
+        for node in &self.nodes {
+            writeln!(
+                dot,
+                "    n{} [label=\"visits: {}\\navg: {:.4}\\nmax: {:.4}\\nprogress: {}\\nquality: {}\"];",
+                node.id,
+                node.visits,
+                node.average_score,
+                node.max_score,
+                node.progress,
+                node.quality
+            )
+            .unwrap();
+        }
+        for edge in &self.edges {
+            let label = edge.action.map_or("", |action| action.label());
+            writeln!(
+                dot,
+                "    n{} -> n{} [label=\"{label}\"];",
+                edge.parent, edge.child
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the tree as a JSON object with `nodes` and `edges` arrays.
+    /// Actions are written by name, or null for nodes without one.
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "{{\"id\":{},\"depth\":{},\"visits\":{},\"average_score\":{},\"max_score\":{},\"progress\":{},\"quality\":{}}}",
+                    node.id,
+                    node.depth,
+                    node.visits,
+                    node.average_score,
+                    node.max_score,
+                    node.progress,
+                    node.quality
+                )
+            })
+            .collect();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let action = edge.action.map_or("null".to_string(), |action| {
+                    format!("\"{}\"", action.name())
+                });
+                format!(
+                    "{{\"parent\":{},\"child\":{},\"action\":{action}}}",
+                    edge.parent, edge.child
+                )
+            })
+            .collect();
+        format!(
+            "{{\"nodes\":[{}],\"edges\":[{}]}}",
+            nodes.join(","),
+            edges.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export() -> TreeExport {
+        let node = |id, depth| ExportedNode {
+            id,
+            depth,
+            visits: 2.0,
+            average_score: 0.5,
+            max_score: 0.75,
+            progress: 100,
+            quality: 200,
+        };
+        TreeExport {
+            nodes: vec![node(0, 0), node(3, 1)],
+            edges: vec![ExportedEdge {
+                parent: 0,
+                child: 3,
+                action: Some(Action::BasicSynthesis),
+            }],
+        }
+    }
+
+    #[test]
+    fn renders_dot() {
+        let dot = export().to_dot();
+        assert!(dot.starts_with("digraph search {"));
+        assert!(dot.contains("n0 -> n3 [label=\"Basic Synthesis\"];"));
+        assert!(dot.contains("n3 [label=\"visits: 2\\navg: 0.5000"));
+    }
+
+    #[test]
+    fn renders_json() {
+        assert_eq!(
+            export().to_json(),
+            concat!(
+                "{\"nodes\":[",
+                "{\"id\":0,\"depth\":0,\"visits\":2,\"average_score\":0.5,\"max_score\":0.75,\"progress\":100,\"quality\":200},",
+                "{\"id\":3,\"depth\":1,\"visits\":2,\"average_score\":0.5,\"max_score\":0.75,\"progress\":100,\"quality\":200}",
+                "],\"edges\":[{\"parent\":0,\"child\":3,\"action\":\"BasicSynthesis\"}]}"
+            )
+        );
+    }
+}
@@ -0,0 +1,76 @@
+// Generates the recipe and recipe level tables from the CSV files in `data/`
+
+// `crafty`'s serde feature doesn't exist here, so `Recipe`'s derives are left
+// out
+#![allow(unexpected_cfgs)]
+
+#[allow(dead_code)]
+#[path = "../crafty/src/recipe.rs"]
+mod recipe;
+
+#[allow(dead_code)]
+#[path = "src/job.rs"]
+mod job;
+
+#[allow(dead_code)]
+#[path = "src/csv.rs"]
+mod csv;
+
+use job::Job;
+use recipe::{Recipe, RecipeLevel, RecipeLevelTable};
+use std::{env, fmt::Write, fs, path::Path};
+
+const RECIPES: &str = "data/recipes.csv";
+const RECIPE_LEVEL_TABLE: &str = "data/recipe_level_table.csv";
+
+/// Reads a CSV file, panicking with its path if it can't be read or parsed
+fn read_csv<T>(path: &str, parse: impl FnOnce(&str) -> Result<T, csv::CsvError>) -> T {
+    println!("cargo:rerun-if-changed={path}");
+    let text = fs::read_to_string(path).unwrap_or_else(|err| panic!("couldn't read {path}: {err}"));
+    parse(&text).unwrap_or_else(|err| panic!("{path}: {err}"))
+}
+
+fn main() {
+    // the generated tables are written with the `Debug` output of these
+    println!("cargo:rerun-if-changed=../crafty/src/recipe.rs");
+    println!("cargo:rerun-if-changed=src/job.rs");
+    println!("cargo:rerun-if-changed=src/csv.rs");
+
+    let table: RecipeLevelTable = read_csv(RECIPE_LEVEL_TABLE, csv::parse_recipe_levels)
+        .into_iter()
+        .collect();
+
+    let mut recipes: Vec<(u32, String, Job, Recipe)> =
+        read_csv(RECIPES, |text| csv::parse_recipes(text, &table))
+            .into_iter()
+            .map(|entry| {
+                let job = entry
+                    .job
+                    .unwrap_or_else(|| panic!("{RECIPES}: recipe {} has no `job`", entry.id));
+                (entry.id, entry.name, job, entry.recipe)
+            })
+            .collect();
+    recipes.sort_by_key(|(id, ..)| *id);
+    if let Some(pair) = recipes.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        panic!("{RECIPES}: recipe {} appears more than once", pair[0].0);
+    }
+
+    // `RecipeLevel`, `Recipe`, and `Job` are written with their `Debug`
+    // output, which is also valid Rust
+    let mut out = String::from("pub(crate) static RECIPE_LEVELS: &[RecipeLevel] = &[\n");
+    for level in table.iter() {
+        writeln!(out, "    {level:?},").unwrap();
+    }
+    out.push_str("];\n\npub(crate) static RECIPES: &[RecipeEntry] = &[\n");
+    for (id, name, job, recipe) in &recipes {
+        writeln!(
+            out,
+            "    RecipeEntry {{ id: {id}, name: {name:?}, job: Job::{job:?}, recipe: {recipe:?} }},"
+        )
+        .unwrap();
+    }
+    out.push_str("];\n");
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("tables.rs"), out).unwrap();
+}
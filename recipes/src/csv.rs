@@ -0,0 +1,342 @@
+use crate::{Job, Recipe, RecipeLevel, RecipeLevelTable};
+use std::{error, fmt, str::FromStr};
+
+// Only uses items the build script also has at its root, since it includes
+// this file to read the bundled tables
+
+/// An error in a CSV file, with the line it was found on if it's in a row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvError {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {line}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl error::Error for CsvError {}
+
+/// Splits a CSV line into fields. Fields can be wrapped in double quotes to
+/// include commas, and `""` inside a quoted field is a literal quote.
+fn split_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// A row of a CSV file, with fields looked up by column name
+struct Row<'a> {
+    line: usize,
+    header: &'a [String],
+    fields: Vec<String>,
+}
+
+impl Row<'_> {
+    fn error(&self, message: String) -> CsvError {
+        CsvError {
+            line: Some(self.line),
+            message,
+        }
+    }
+
+    /// The field in `column`, or None if the column or the field is missing or
+    /// empty
+    fn get(&self, column: &str) -> Option<&str> {
+        let index = self.header.iter().position(|name| name == column)?;
+        let field = self.fields.get(index)?.trim();
+        (!field.is_empty()).then_some(field)
+    }
+
+    fn parse_optional<T: FromStr>(&self, column: &str) -> Result<Option<T>, CsvError> {
+        self.get(column)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| self.error(format!("invalid `{column}` value `{value}`")))
+            })
+            .transpose()
+    }
+
+    fn parse<T: FromStr>(&self, column: &str) -> Result<T, CsvError> {
+        self.parse_optional(column)?
+            .ok_or_else(|| self.error(format!("missing `{column}`")))
+    }
+
+    fn parse_bool(&self, column: &str) -> Result<Option<bool>, CsvError> {
+        self.get(column)
+            .map(|value| match value.to_lowercase().as_str() {
+                "true" | "1" => Ok(true),
+                "false" | "0" => Ok(false),
+                _ => Err(self.error(format!("invalid `{column}` value `{value}`"))),
+            })
+            .transpose()
+    }
+}
+
+/// Reads every row of CSV text. The first line is the header, and empty lines
+/// and lines starting with `#` are skipped. `required` columns must be in the
+/// header, though their fields can still be missing.
+fn read_rows<T>(
+    text: &str,
+    required: &[&str],
+    mut read_row: impl FnMut(&Row) -> Result<T, CsvError>,
+) -> Result<Vec<T>, CsvError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+
+    let error = |message: String| CsvError {
+        line: None,
+        message,
+    };
+    let (_, header) = lines.next().ok_or(error("file is empty".to_string()))?;
+    let header: Vec<String> = split_line(header)
+        .into_iter()
+        .map(|column| column.trim().to_string())
+        .collect();
+    if let Some(column) = required
+        .iter()
+        .find(|&&column| !header.iter().any(|name| name == column))
+    {
+        return Err(error(format!("missing the `{column}` column")));
+    }
+
+    lines
+        .map(|(index, line)| {
+            read_row(&Row {
+                line: index + 1,
+                header: &header,
+                fields: split_line(line),
+            })
+        })
+        .collect()
+}
+
+/// Parses recipe levels in the format of `data/recipe_level_table.csv`, with
+/// the columns `recipe_level`, `job_level`, `stars`, `progress`, `quality`,
+/// `durability`, `progress_div`, `progress_mod`, `quality_div`, `quality_mod`,
+/// and `conditions_flag`
+///
+/// # Errors
+///
+/// Returns a `CsvError` for the first missing column or invalid value.
+pub fn parse_recipe_levels(text: &str) -> Result<Vec<RecipeLevel>, CsvError> {
+    const COLUMNS: [&str; 11] = [
+        "recipe_level",
+        "job_level",
+        "stars",
+        "progress",
+        "quality",
+        "durability",
+        "progress_div",
+        "progress_mod",
+        "quality_div",
+        "quality_mod",
+        "conditions_flag",
+    ];
+    read_rows(text, &COLUMNS, |row| {
+        Ok(RecipeLevel {
+            recipe_level: row.parse("recipe_level")?,
+            job_level: row.parse("job_level")?,
+            stars: row.parse("stars")?,
+            progress: row.parse("progress")?,
+            quality: row.parse("quality")?,
+            durability: row.parse("durability")?,
+            progress_div: row.parse("progress_div")?,
+            progress_mod: row.parse("progress_mod")?,
+            quality_div: row.parse("quality_div")?,
+            quality_mod: row.parse("quality_mod")?,
+            conditions_flag: row.parse("conditions_flag")?,
+        })
+    })
+}
+
+/// A recipe read from a CSV file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvRecipe {
+    pub id: u32,
+    pub name: String,
+    /// None if the file has no `job` column, or the field is empty
+    pub job: Option<Job>,
+    pub recipe: Recipe,
+}
+
+/// Parses recipes in the format of `data/recipes.csv`, with the columns `id`,
+/// `name`, `recipe_level`, `progress`, `quality`, and `durability`, and
+/// optionally `job` and `is_expert`.
+///
+/// The rest of each recipe is filled in from `table`. Recipes with a recipe
+/// level that isn't in the table need the columns `job_level`,
+/// `progress_div`, `progress_mod`, `quality_div`, and `quality_mod`, and
+/// optionally `stars` and `conditions_flag`. These can be given for other
+/// recipes too, and are checked against the table.
+///
+/// # Errors
+///
+/// Returns a `CsvError` for the first missing column, invalid value, or
+/// recipe that doesn't match `table`.
+pub fn parse_recipes(text: &str, table: &RecipeLevelTable) -> Result<Vec<CsvRecipe>, CsvError> {
+    const COLUMNS: [&str; 6] = [
+        "id",
+        "name",
+        "recipe_level",
+        "progress",
+        "quality",
+        "durability",
+    ];
+    read_rows(text, &COLUMNS, |row| {
+        let recipe_level = row.parse("recipe_level")?;
+        let level = table.get(recipe_level);
+        let from_level = |column: &str, field: fn(&RecipeLevel) -> u32| {
+            Ok::<_, CsvError>(row.parse_optional(column)?.or(level.map(field)))
+        };
+        let required = |column: &str, field: fn(&RecipeLevel) -> u32| {
+            from_level(column, field)?.ok_or_else(|| {
+                row.error(format!(
+                    "recipe level {recipe_level} isn't in the recipe level table, so `{column}` is required"
+                ))
+            })
+        };
+
+        let job = row
+            .get("job")
+            .map(|job| {
+                job.parse()
+                    .map_err(|_| row.error(format!("invalid `job` value `{job}`")))
+            })
+            .transpose()?;
+        let recipe = Recipe {
+            recipe_level,
+            job_level: required("job_level", |level| level.job_level)?,
+            stars: from_level("stars", |level| level.stars)?.unwrap_or(0),
+            progress: row.parse("progress")?,
+            quality: row.parse("quality")?,
+            durability: row.parse("durability")?,
+            progress_div: required("progress_div", |level| level.progress_div)?,
+            progress_mod: required("progress_mod", |level| level.progress_mod)?,
+            quality_div: required("quality_div", |level| level.quality_div)?,
+            quality_mod: required("quality_mod", |level| level.quality_mod)?,
+            is_expert: row.parse_bool("is_expert")?.unwrap_or(false),
+            conditions_flag: from_level("conditions_flag", |level| level.conditions_flag)?
+                .unwrap_or(15),
+        };
+        if level.is_some() {
+            table
+                .validate(&recipe)
+                .map_err(|err| row.error(err.to_string()))?;
+        }
+        Ok(CsvRecipe {
+            id: row.parse("id")?,
+            name: row.get("name").unwrap_or_default().to_string(),
+            job,
+            recipe,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECIPE_LEVELS: &str = "\
+recipe_level,job_level,stars,progress,quality,durability,progress_div,progress_mod,quality_div,quality_mod,conditions_flag
+560,90,0,3500,7200,80,130,90,115,80,15
+";
+
+    const RECIPES: &str = "\
+id,name,job,recipe_level,progress,quality,durability,is_expert
+# comments are skipped
+35000,\"Chondrite Saw, Unfinished\",CRP,560,3500,7200,80,false
+
+35001,Rinascita Sword,,560,3500,7200,70,1
+";
+
+    fn table() -> RecipeLevelTable {
+        parse_recipe_levels(RECIPE_LEVELS)
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn parses_recipes() {
+        let recipes = parse_recipes(RECIPES, &table()).unwrap();
+        assert_eq!(recipes.len(), 2);
+        assert_eq!(recipes[0].id, 35000);
+        assert_eq!(recipes[0].name, "Chondrite Saw, Unfinished");
+        assert_eq!(recipes[0].job, Some(Job::Carpenter));
+        assert_eq!(recipes[0].recipe.progress, 3500);
+        assert_eq!(recipes[0].recipe.progress_div, 130);
+        assert!(!recipes[0].recipe.is_expert);
+        assert_eq!(recipes[1].job, None);
+        assert_eq!(recipes[1].recipe.durability, 70);
+        assert!(recipes[1].recipe.is_expert);
+    }
+
+    #[test]
+    fn parses_recipes_outside_the_table() {
+        let text = "\
+id,name,job_level,recipe_level,stars,progress,quality,durability,progress_div,progress_mod,quality_div,quality_mod
+35002,Rinascita Saw,90,640,2,6600,14040,70,130,80,115,70
+";
+        let recipes = parse_recipes(text, &table()).unwrap();
+        assert_eq!(recipes[0].recipe.stars, 2);
+        assert_eq!(recipes[0].recipe.quality_mod, 70);
+        assert_eq!(recipes[0].recipe.conditions_flag, 15);
+
+        let text = "id,name,recipe_level,progress,quality,durability\n1,a,640,1,1,1\n";
+        assert_eq!(
+            parse_recipes(text, &table()).unwrap_err().to_string(),
+            "line 2: recipe level 640 isn't in the recipe level table, so `job_level` is required"
+        );
+    }
+
+    #[test]
+    fn reports_bad_values() {
+        let text = RECIPES.replace(",3500,", ",lots,");
+        let err = parse_recipes(&text, &table()).unwrap_err();
+        assert_eq!(err.to_string(), "line 3: invalid `progress` value `lots`");
+
+        let text = "\
+id,name,job_level,recipe_level,progress,quality,durability
+35000,Chondrite Saw,91,560,3500,7200,80
+";
+        let err = parse_recipes(text, &table()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: recipe job level should be 90 for this recipe level, got 91"
+        );
+
+        let err = parse_recipes("id,name\n1,a\n", &table()).unwrap_err();
+        assert_eq!(err.to_string(), "missing the `recipe_level` column");
+    }
+
+    #[test]
+    fn splits_quoted_fields() {
+        assert_eq!(
+            split_line("a,\"b, \"\"c\"\"\",d"),
+            vec!["a", "b, \"c\"", "d"]
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use std::{fmt, str::FromStr};
+
+// Must not depend on the rest of the crate so it can be used in the build script
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Job {
+    Carpenter,
+    Blacksmith,
+    Armorer,
+    Goldsmith,
+    Leatherworker,
+    Weaver,
+    Alchemist,
+    Culinarian,
+}
+
+impl Job {
+    pub const ALL: [Job; 8] = [
+        Job::Carpenter,
+        Job::Blacksmith,
+        Job::Armorer,
+        Job::Goldsmith,
+        Job::Leatherworker,
+        Job::Weaver,
+        Job::Alchemist,
+        Job::Culinarian,
+    ];
+
+    pub fn abbreviation(self) -> &'static str {
+        match self {
+            Job::Carpenter => "CRP",
+            Job::Blacksmith => "BSM",
+            Job::Armorer => "ARM",
+            Job::Goldsmith => "GSM",
+            Job::Leatherworker => "LTW",
+            Job::Weaver => "WVR",
+            Job::Alchemist => "ALC",
+            Job::Culinarian => "CUL",
+        }
+    }
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[derive(Debug)]
+pub struct JobParseError;
+
+/// Parses either the abbreviation (`CRP`) or the full name (`Carpenter`),
+/// ignoring case
+impl FromStr for Job {
+    type Err = JobParseError;
+
+    fn from_str(s: &str) -> Result<Job, JobParseError> {
+        Job::ALL
+            .into_iter()
+            .find(|job| {
+                job.abbreviation().eq_ignore_ascii_case(s)
+                    || job.to_string().eq_ignore_ascii_case(s)
+            })
+            .ok_or(JobParseError)
+    }
+}
@@ -0,0 +1,197 @@
+#![warn(clippy::pedantic)]
+#![allow(clippy::must_use_candidate)]
+
+pub mod csv;
+mod job;
+#[cfg(test)]
+mod test_fixtures;
+
+use crafty::{Recipe, RecipeLevel, RecipeLevelTable};
+use std::{ops::RangeBounds, sync::OnceLock};
+
+pub use job::{Job, JobParseError};
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct RecipeEntry {
+    pub id: u32,
+    /// Name of the crafted item
+    pub name: &'static str,
+    pub job: Job,
+    pub recipe: Recipe,
+}
+
+// Generated by build.rs from the files in `data/`, sorted by ID and recipe
+// level. Both are empty until rows are exported from the game.
+include!(concat!(env!("OUT_DIR"), "/tables.rs"));
+
+/// Every bundled recipe, sorted by ID
+pub fn all() -> &'static [RecipeEntry] {
+    RECIPES
+}
+
+pub fn by_id(id: u32) -> Option<&'static RecipeEntry> {
+    by_id_in(RECIPES, id)
+}
+
+fn by_id_in(recipes: &[RecipeEntry], id: u32) -> Option<&RecipeEntry> {
+    recipes
+        .binary_search_by_key(&id, |entry| entry.id)
+        .ok()
+        .map(|index| &recipes[index])
+}
+
+pub fn recipe_level(recipe_level: u32) -> Option<&'static RecipeLevel> {
+    RECIPE_LEVELS
+        .binary_search_by_key(&recipe_level, |level| level.recipe_level)
+        .ok()
+        .map(|index| &RECIPE_LEVELS[index])
+}
+
+/// The bundled recipe level table, for creating and validating recipes that
+/// aren't in the recipe table
+pub fn recipe_level_table() -> &'static RecipeLevelTable {
+    static TABLE: OnceLock<RecipeLevelTable> = OnceLock::new();
+    TABLE.get_or_init(|| RECIPE_LEVELS.iter().cloned().collect())
+}
+
+/// Recipes for `job` (or every job if None) with a job level in `levels`
+pub fn by_level(
+    job: Option<Job>,
+    levels: impl RangeBounds<u32>,
+) -> impl Iterator<Item = &'static RecipeEntry> {
+    by_level_in(RECIPES, job, levels)
+}
+
+fn by_level_in(
+    recipes: &[RecipeEntry],
+    job: Option<Job>,
+    levels: impl RangeBounds<u32>,
+) -> impl Iterator<Item = &RecipeEntry> {
+    recipes.iter().filter(move |entry| {
+        job.is_none_or(|job| entry.job == job) && levels.contains(&entry.recipe.job_level)
+    })
+}
+
+/// How well `name` matches `query`, both lowercase. Lower is better; None if
+/// they don't match at all.
+fn match_rank(name: &str, query: &str) -> Option<(u8, usize)> {
+    if name == query {
+        return Some((0, 0));
+    }
+    if name.starts_with(query) {
+        return Some((1, 0));
+    }
+    if query.split_whitespace().all(|word| name.contains(word)) {
+        return Some((2, 0));
+    }
+
+    // the query's letters appear in order, e.g. "chon saw" for "chondrite
+    // saw". Tighter matches rank higher.
+    let mut name_chars = name.char_indices();
+    let mut start = None;
+    let mut end = 0;
+    for c in query.chars().filter(|c| !c.is_whitespace()) {
+        let (index, _) = name_chars.find(|&(_, name_char)| name_char == c)?;
+        start.get_or_insert(index);
+        end = index;
+    }
+    Some((3, end - start.unwrap_or(end)))
+}
+
+/// Recipes with names matching `query`, ignoring case. Exact matches come
+/// first, then prefixes, names containing every word of the query, and names
+/// containing the query's letters in order.
+pub fn search(query: &str) -> Vec<&'static RecipeEntry> {
+    search_in(RECIPES, query)
+}
+
+fn search_in<'a>(recipes: &'a [RecipeEntry], query: &str) -> Vec<&'a RecipeEntry> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return vec![];
+    }
+
+    let mut matches: Vec<_> = recipes
+        .iter()
+        .filter_map(|entry| {
+            let rank = match_rank(&entry.name.to_lowercase(), &query)?;
+            Some((rank, entry.name.len(), entry))
+        })
+        .collect();
+    matches.sort_by_key(|&(rank, length, entry)| (rank, length, entry.id));
+    matches.into_iter().map(|(.., entry)| entry).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_tables_are_sorted_and_valid() {
+        assert!(RECIPES.windows(2).all(|pair| pair[0].id < pair[1].id));
+        assert!(RECIPE_LEVELS
+            .windows(2)
+            .all(|pair| pair[0].recipe_level < pair[1].recipe_level));
+        let table = recipe_level_table();
+        assert!(all()
+            .iter()
+            .all(|entry| table.validate(&entry.recipe).is_ok()));
+    }
+
+    #[test]
+    fn joins_recipe_levels() {
+        let recipes = test_fixtures::recipes();
+        let entry = by_id_in(&recipes, 35200).unwrap();
+        assert_eq!(entry.job, Job::Alchemist);
+        let table = test_fixtures::recipe_level_table();
+        let level = table.get(entry.recipe.recipe_level).unwrap();
+        assert_eq!(entry.recipe.job_level, level.job_level);
+        assert_eq!(entry.recipe.stars, 3);
+        assert_eq!(entry.recipe.progress_mod, level.progress_mod);
+        assert_eq!(entry.recipe.conditions_flag, 483);
+        assert!(entry.recipe.is_expert);
+
+        assert!(by_id_in(&recipes, 1).is_none());
+        assert!(recipe_level(1).is_none());
+    }
+
+    #[test]
+    fn searches_names() {
+        let recipes = test_fixtures::recipes();
+        let names = |query| {
+            search_in(&recipes, query)
+                .iter()
+                .map(|entry| entry.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("chondrite saw"), vec!["Chondrite Saw"]);
+        assert_eq!(
+            names("CHONDRITE")[..2],
+            ["Chondrite Saw", "Chondrite Lapidary Hammer"]
+        );
+        assert_eq!(
+            names("needle chondrite"),
+            vec!["Chondrite Needle, Unfinished"]
+        );
+        assert_eq!(names("clr wlnt lmbr")[0], "Claro Walnut Lumber");
+        assert!(names("mythril").is_empty());
+        assert!(names(" ").is_empty());
+    }
+
+    #[test]
+    fn filters_by_level() {
+        let recipes = test_fixtures::recipes();
+        assert_eq!(by_level_in(&recipes, None, 100..).count(), 2);
+        let ids: Vec<u32> = by_level_in(&recipes, Some(Job::Blacksmith), 20..=90)
+            .map(|entry| entry.id)
+            .collect();
+        assert_eq!(ids, vec![101, 35100]);
+    }
+
+    #[test]
+    fn parses_jobs() {
+        assert_eq!("crp".parse::<Job>().unwrap(), Job::Carpenter);
+        assert_eq!("Weaver".parse::<Job>().unwrap(), Job::Weaver);
+        assert!("Miner".parse::<Job>().is_err());
+    }
+}
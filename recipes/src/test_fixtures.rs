@@ -0,0 +1,47 @@
+// Made-up recipes and recipe levels for tests, since the bundled tables only
+// hold rows exported from the game
+use crate::{csv, RecipeEntry};
+use crafty::RecipeLevelTable;
+
+const RECIPE_LEVELS: &str = "\
+recipe_level,job_level,stars,progress,quality,durability,progress_div,progress_mod,quality_div,quality_mod,conditions_flag
+25,25,0,150,1200,40,50,100,30,100,15
+560,90,0,3500,7200,80,130,90,115,80,15
+580,90,2,3900,10920,70,130,80,115,70,15
+590,90,3,5060,12628,55,130,70,115,60,483
+690,100,0,6600,12000,80,170,90,150,75,15
+";
+
+const RECIPES: &str = "\
+id,name,job,recipe_level,progress,quality,durability,is_expert
+101,Iron Ingot,BSM,25,150,1200,40,false
+102,Iron Rivets,ARM,25,150,1200,40,false
+35000,Chondrite Saw,CRP,560,3500,7200,80,false
+35001,Chondrite Lapidary Hammer,GSM,560,3500,7200,80,false
+35002,\"Chondrite Needle, Unfinished\",LTW,560,3500,7200,80,false
+35100,Ceremonial Longsword,BSM,580,3900,10920,70,false
+35200,Stunning Sphere,ALC,590,5060,12628,55,true
+40000,Claro Walnut Lumber,CRP,690,6600,12000,80,false
+40001,Claro Walnut Spinning Wheel,WVR,690,6600,12000,80,false
+";
+
+pub fn recipe_level_table() -> RecipeLevelTable {
+    csv::parse_recipe_levels(RECIPE_LEVELS)
+        .unwrap()
+        .into_iter()
+        .collect()
+}
+
+/// Sorted by ID, like the bundled recipes
+pub fn recipes() -> Vec<RecipeEntry> {
+    csv::parse_recipes(RECIPES, &recipe_level_table())
+        .unwrap()
+        .into_iter()
+        .map(|entry| RecipeEntry {
+            id: entry.id,
+            name: entry.name.leak(),
+            job: entry.job.unwrap(),
+            recipe: entry.recipe,
+        })
+        .collect()
+}
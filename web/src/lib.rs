@@ -0,0 +1,366 @@
+use crafty::{
+    Action, CraftContext, CraftOptions, CraftResult, CraftState, Player, Recipe, SearchOptions,
+    Simulator,
+};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlayerInput {
+    job_level: u32,
+    craftsmanship: u32,
+    control: u32,
+    cp: u32,
+}
+
+impl From<PlayerInput> for Player {
+    fn from(input: PlayerInput) -> Self {
+        Player::new(
+            input.job_level,
+            input.craftsmanship,
+            input.control,
+            input.cp,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecipeInput {
+    recipe_level: u32,
+    job_level: u32,
+    #[serde(default)]
+    stars: u32,
+    progress: u32,
+    quality: u32,
+    durability: i8,
+    progress_div: u32,
+    progress_mod: u32,
+    quality_div: u32,
+    quality_mod: u32,
+    #[serde(default)]
+    is_expert: bool,
+    #[serde(default = "default_conditions_flag")]
+    conditions_flag: u32,
+}
+
+fn default_conditions_flag() -> u32 {
+    15
+}
+
+impl From<RecipeInput> for Recipe {
+    fn from(input: RecipeInput) -> Self {
+        Recipe {
+            recipe_level: input.recipe_level,
+            job_level: input.job_level,
+            stars: input.stars,
+            progress: input.progress,
+            quality: input.quality,
+            durability: input.durability,
+            progress_div: input.progress_div,
+            progress_mod: input.progress_mod,
+            quality_div: input.quality_div,
+            quality_mod: input.quality_mod,
+            is_expert: input.is_expert,
+            conditions_flag: input.conditions_flag,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct CraftOptionsInput {
+    /// Defaults to 50
+    max_steps: Option<u8>,
+    starting_quality: Option<u32>,
+    quality_target: Option<u32>,
+    is_specialist: bool,
+}
+
+impl From<CraftOptionsInput> for CraftOptions {
+    fn from(input: CraftOptionsInput) -> Self {
+        CraftOptions {
+            max_steps: input.max_steps.unwrap_or(50),
+            starting_quality: input.starting_quality,
+            quality_target: input.quality_target,
+            is_specialist: input.is_specialist,
+        }
+    }
+}
+
+/// Search options that work in browsers. There's no time limit, since the
+/// simulator can't read the clock in WebAssembly.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SearchOptionsInput {
+    iterations: Option<u32>,
+    rng_seed: Option<u32>,
+    score_storage_threshold: Option<f32>,
+    max_score_weighting_constant: Option<f32>,
+    exploration_constant: Option<f32>,
+}
+
+impl SearchOptionsInput {
+    /// Uses `fallback_seed` if no seed was given, so the search doesn't need
+    /// an entropy source
+    fn into_options(self, fallback_seed: impl FnOnce() -> u32) -> SearchOptions {
+        let defaults = SearchOptions::default();
+        SearchOptions {
+            iterations: self.iterations.unwrap_or(defaults.iterations),
+            rng_seed: Some(self.rng_seed.unwrap_or_else(fallback_seed)),
+            score_storage_threshold: self.score_storage_threshold,
+            max_score_weighting_constant: self.max_score_weighting_constant,
+            exploration_constant: self.exploration_constant,
+            ..defaults
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StateOutput {
+    step: u8,
+    progress: u32,
+    progress_target: u32,
+    quality: u32,
+    quality_target: u32,
+    durability: i8,
+    max_durability: i8,
+    cp: u32,
+    max_cp: u32,
+    condition: String,
+    inner_quiet: u8,
+}
+
+impl From<&CraftState<'_>> for StateOutput {
+    fn from(state: &CraftState) -> Self {
+        StateOutput {
+            step: state.step,
+            progress: state.progress,
+            progress_target: state.context.progress_target,
+            quality: state.quality,
+            quality_target: state.context.quality_target,
+            durability: state.durability,
+            max_durability: state.context.durability_max,
+            cp: state.cp,
+            max_cp: state.context.cp_max,
+            condition: format!("{:?}", state.condition),
+            inner_quiet: state.buffs.inner_quiet,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind")]
+enum ResultOutput {
+    Unfinished,
+    Finished { score: f32 },
+    DurabilityFailure,
+    MaxStepsFailure,
+    InvalidActionFailure,
+}
+
+impl From<Option<CraftResult>> for ResultOutput {
+    fn from(result: Option<CraftResult>) -> Self {
+        match result {
+            None => ResultOutput::Unfinished,
+            Some(CraftResult::Finished(score)) => ResultOutput::Finished { score },
+            Some(CraftResult::DurabilityFailure) => ResultOutput::DurabilityFailure,
+            Some(CraftResult::MaxStepsFailure) => ResultOutput::MaxStepsFailure,
+            Some(CraftResult::InvalidActionFailure) => ResultOutput::InvalidActionFailure,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CraftOutput {
+    /// Action names, e.g. `BasicSynthesis`
+    actions: Vec<&'static str>,
+    state: StateOutput,
+    result: ResultOutput,
+}
+
+impl CraftOutput {
+    fn new(actions: &[Action], state: &CraftState) -> Self {
+        CraftOutput {
+            actions: actions.iter().map(Action::name).collect(),
+            state: state.into(),
+            result: state.check_result().into(),
+        }
+    }
+}
+
+fn parse_actions(actions: &[String]) -> Result<Vec<Action>, String> {
+    actions
+        .iter()
+        .map(|action| {
+            action
+                .parse()
+                .map_err(|_| format!("unknown action `{action}`"))
+        })
+        .collect()
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    serde_wasm_bindgen::to_value(value).map_err(|err| JsError::new(&err.to_string()))
+}
+
+fn from_js<T: for<'de> Deserialize<'de>>(value: JsValue) -> Result<T, JsError> {
+    serde_wasm_bindgen::from_value(value).map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// A craft's context, created once per recipe and player. Player and recipe
+/// objects use camelCase keys matching the fields of `Player` and `Recipe`.
+#[wasm_bindgen(js_name = CraftContext)]
+pub struct WebCraftContext {
+    context: CraftContext,
+}
+
+#[wasm_bindgen(js_class = CraftContext)]
+impl WebCraftContext {
+    /// # Errors
+    ///
+    /// Throws if an object is malformed or the recipe can't be crafted
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        player: JsValue,
+        recipe: JsValue,
+        options: JsValue,
+    ) -> Result<WebCraftContext, JsError> {
+        let player: Player = from_js::<PlayerInput>(player)?.into();
+        let recipe: Recipe = from_js::<RecipeInput>(recipe)?.into();
+        let options: CraftOptions = if options.is_undefined() {
+            CraftOptionsInput::default()
+        } else {
+            from_js(options)?
+        }
+        .into();
+        let context = CraftContext::try_new(&player, &recipe, options)
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(WebCraftContext { context })
+    }
+
+    /// Simulates a list of action names, returning the actions, the final
+    /// state, and the result of the craft
+    ///
+    /// # Errors
+    ///
+    /// Throws if an action name is unknown
+    pub fn simulate(&self, actions: Vec<String>) -> Result<JsValue, JsError> {
+        let actions = parse_actions(&actions).map_err(|err| JsError::new(&err))?;
+        let (state, _) = Simulator::simulate(&self.context, actions.clone());
+        to_js(&CraftOutput::new(&actions, &state))
+    }
+
+    /// Searches for a rotation one action at a time after `actions`.
+    /// `action_callback` is called with the name of each action as soon as
+    /// it's picked. A seed is drawn from `Math.random` if `rngSeed` isn't set.
+    ///
+    /// # Errors
+    ///
+    /// Throws if an action name is unknown or the options are malformed
+    #[wasm_bindgen(js_name = searchStepwise)]
+    pub fn search_stepwise(
+        &self,
+        actions: Vec<String>,
+        options: JsValue,
+        action_callback: Option<js_sys::Function>,
+    ) -> Result<JsValue, JsError> {
+        let actions = parse_actions(&actions).map_err(|err| JsError::new(&err))?;
+        let options = if options.is_undefined() {
+            SearchOptionsInput::default()
+        } else {
+            from_js(options)?
+        };
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let options =
+            options.into_options(|| (js_sys::Math::random() * f64::from(u32::MAX)) as u32);
+
+        let callback = action_callback.map(|callback| {
+            move |action: Action| {
+                // errors thrown by the callback are ignored
+                let _ = callback.call1(&JsValue::NULL, &JsValue::from_str(action.name()));
+            }
+        });
+        let (actions, state) = Simulator::search_stepwise(
+            &self.context,
+            actions,
+            options,
+            callback
+                .as_ref()
+                .map(|callback| callback as &dyn Fn(Action)),
+        );
+        to_js(&CraftOutput::new(&actions, &state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The usual level 90 test recipe and player, built through the input
+    // types so their camelCase keys and defaults are covered too
+    fn setup() -> CraftContext {
+        let player: Player = PlayerInput {
+            job_level: 90,
+            craftsmanship: 3304,
+            control: 3374,
+            cp: 575,
+        }
+        .into();
+        let recipe: Recipe = serde_json::from_str::<RecipeInput>(
+            r#"{
+                "recipeLevel": 560,
+                "jobLevel": 90,
+                "progress": 3500,
+                "quality": 7200,
+                "durability": 80,
+                "progressDiv": 130,
+                "progressMod": 90,
+                "qualityDiv": 115,
+                "qualityMod": 80
+            }"#,
+        )
+        .unwrap()
+        .into();
+        assert_eq!(recipe.conditions_flag, 15);
+        CraftContext::new(&player, &recipe, CraftOptionsInput::default().into())
+    }
+
+    #[test]
+    fn serializes_simulation() {
+        let context = setup();
+        let actions = parse_actions(&["MuscleMemory".into(), "Veneration".into()]).unwrap();
+        let (state, _) = Simulator::simulate(&context, actions.clone());
+        let json = serde_json::to_value(CraftOutput::new(&actions, &state)).unwrap();
+
+        assert_eq!(json["actions"][0], "MuscleMemory");
+        assert_eq!(json["state"]["step"], 3);
+        assert_eq!(json["state"]["progressTarget"], 3500);
+        assert_eq!(json["state"]["condition"], "Normal");
+        assert_eq!(json["result"]["kind"], "Unfinished");
+    }
+
+    #[test]
+    fn rejects_unknown_actions() {
+        assert_eq!(
+            parse_actions(&["Veneration".into(), "Basic Synthesis".into()]),
+            Err("unknown action `Basic Synthesis`".to_string())
+        );
+    }
+
+    #[test]
+    fn uses_fallback_seed_only_without_seed() {
+        let options = SearchOptionsInput::default().into_options(|| 7);
+        assert_eq!(options.rng_seed, Some(7));
+
+        let options = SearchOptionsInput {
+            rng_seed: Some(1),
+            ..Default::default()
+        }
+        .into_options(|| panic!("seed was given"));
+        assert_eq!(options.rng_seed, Some(1));
+    }
+}